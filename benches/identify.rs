@@ -0,0 +1,44 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use dfint_installer::{df_binary::DfBinary, identify::Identity};
+use regex::bytes::Regex;
+use std::hint::black_box;
+use std::path::Path;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/binaries");
+/// About the size of the DF executables, the fixtures are padded to it.
+const EXECUTABLE_SIZE: usize = 32 * 1024 * 1024;
+
+/// Identification as it was before: the whole file is searched for the version and the
+/// Steam import.
+fn whole_file_scan(data: &[u8]) -> (Option<String>, bool, u32) {
+  let steam = Regex::new(r"SteamAPI_Init").unwrap();
+  (
+    DfBinary::detect_df_version(data),
+    steam.is_match(data),
+    crc32fast::hash(data),
+  )
+}
+
+fn identify(c: &mut Criterion) {
+  for name in [
+    "dwarfort",
+    "Dwarf Fortress.exe",
+    "Dwarf Fortress.app/Contents/MacOS/dwarfort",
+  ] {
+    let mut data = std::fs::read(Path::new(FIXTURES).join(name)).unwrap();
+    data.resize(EXECUTABLE_SIZE, 0);
+    c.bench_function(&format!("identify {name}"), |b| {
+      b.iter(|| Identity::of(black_box(&data)).unwrap())
+    });
+    c.bench_function(&format!("whole file scan {name}"), |b| {
+      b.iter(|| whole_file_scan(black_box(&data)))
+    });
+  }
+}
+
+criterion_group! {
+  name = benches;
+  config = Criterion::default().sample_size(20);
+  targets = identify
+}
+criterion_main!(benches);
@@ -0,0 +1,182 @@
+"""Builds the small DF-like executables in tests/fixtures/binaries.
+
+dwarfort is an ELF built with gcc that imports SteamAPI_Init and has a build id.
+"Dwarf Fortress.exe" is a hand-made PE32+ image with a version resource and the
+import name in .rdata, "Dwarf Fortress.app" a bundle with a hand-made universal
+Mach-O that has an UUID and an undefined _SteamAPI_Init. Neither of them runs.
+"""
+
+import struct
+import subprocess
+import tempfile
+from pathlib import Path
+
+project_root_path = Path(__file__).parent.parent
+output_path = project_root_path / "tests" / "fixtures" / "binaries"
+
+ELF_SOURCE = r"""
+__attribute__((weak)) extern int SteamAPI_Init(void);
+static const char versions[] = "\0" "50.14" "\0" "51.01-beta2" "\0" "51.01" "\0";
+
+int main(void) {
+  return SteamAPI_Init ? SteamAPI_Init() : versions[1];
+}
+"""
+
+PE_TIMESTAMP = 0x6512ABCD
+FILE_ALIGNMENT = 0x200
+SECTION_ALIGNMENT = 0x1000
+
+
+def align(data, size):
+    return data + b"\0" * (-len(data) % size)
+
+
+def utf16(text):
+    return (text + "\0").encode("utf-16-le")
+
+
+def version_node(key, value=b"", value_length=0, text=False, children=()):
+    """Node of a VS_VERSIONINFO tree, children start on 32-bit boundaries."""
+    body = align(struct.pack("<HHH", 0, value_length, int(text)) + utf16(key), 4) + value
+    for child in children:
+        body = align(body, 4) + child
+    return struct.pack("<H", len(body)) + body[2:]
+
+
+def version_info(product_version):
+    fixed = struct.pack("<13I", 0xFEEF04BD, 0x10000, 51, 1, 51, 1, 0x3F, 0, 0x40004, 1, 0, 0, 0)
+    string = version_node("ProductVersion", utf16(product_version), len(product_version) + 1, True)
+    table = version_node("040904b0", text=True, children=[string])
+    strings = version_node("StringFileInfo", text=True, children=[table])
+    return version_node("VS_VERSION_INFO", fixed, len(fixed), children=[strings])
+
+
+def resource_section(rva, blob):
+    """Type 16 (RT_VERSION) > id 1 > language 0x409 > the blob."""
+
+    def directory(entry_id, offset):
+        return struct.pack("<IIHHHH", 0, 0, 0, 0, 0, 1) + struct.pack("<II", entry_id, offset)
+
+    tree = directory(16, 0x80000018) + directory(1, 0x80000030) + directory(0x409, 0x48)
+    tree += struct.pack("<IIII", rva + 0x58, len(blob), 0, 0)
+    return tree + blob
+
+
+def pe_image():
+    sections = [
+        (b".text", 0x60000020, b"\xc3"),
+        (b".rdata", 0x40000040, b"\0SteamAPI_Init\0steam_api64.dll\0" + b"\x0050.14\0"),
+        (b".rsrc", 0x40000040, resource_section(0x3000, version_info("51.01"))),
+    ]
+    headers_size = FILE_ALIGNMENT
+    image_size = SECTION_ALIGNMENT * (len(sections) + 1)
+    resource_size = len(sections[2][2])
+
+    optional = struct.pack("<HBBIIIII", 0x20B, 14, 0, FILE_ALIGNMENT, 2 * FILE_ALIGNMENT, 0, 0x1000, 0x1000)
+    optional += struct.pack("<QII", 0x140000000, SECTION_ALIGNMENT, FILE_ALIGNMENT)
+    optional += struct.pack("<HHHHHHIIIIHH", 6, 0, 0, 0, 6, 0, 0, image_size, headers_size, 0, 3, 0x8160)
+    optional += struct.pack("<QQQQII", 0x100000, 0x1000, 0x100000, 0x1000, 0, 16)
+    directories = [(0, 0)] * 16
+    directories[2] = (0x3000, resource_size)
+    optional += b"".join(struct.pack("<II", *entry) for entry in directories)
+
+    coff = struct.pack("<HHIIIHH", 0x8664, len(sections), PE_TIMESTAMP, 0, 0, len(optional), 0x22)
+    dos = b"MZ" + b"\0" * 0x3A + struct.pack("<I", 0x40)
+    table = b""
+    raw = b""
+    for index, (name, characteristics, data) in enumerate(sections):
+        data = align(data, FILE_ALIGNMENT)
+        table += struct.pack(
+            "<8sIIIIIIHHI",
+            name,
+            len(sections[index][2]),
+            SECTION_ALIGNMENT * (index + 1),
+            len(data),
+            headers_size + len(raw),
+            0,
+            0,
+            0,
+            0,
+            characteristics,
+        )
+        raw += data
+    return align(dos + b"PE\0\0" + coff + optional + table, FILE_ALIGNMENT) + raw
+
+
+MACHO_UUID = bytes.fromhex("3f1c5e2a9b7d4e0f8a6b2c1d0e9f8a7b")
+
+
+def macho_image():
+    """x86_64 executable with a __TEXT,__cstring section, LC_UUID and LC_SYMTAB."""
+    strings = b"\x0050.14\x0051.01\x00"
+    symbols = b"\0_SteamAPI_Init\0"
+    commands_size = (72 + 80) + 24 + 24
+    strings_offset = 0x100
+    symbols_offset = strings_offset + len(strings)
+    symbols_offset += -symbols_offset % 8
+    names_offset = symbols_offset + 16
+    size = names_offset + len(symbols)
+
+    header = struct.pack("<IiiIIIII", 0xFEEDFACF, 0x01000007, 3, 2, 3, commands_size, 0x200084, 0)
+    segment = struct.pack("<II16sQQQQiiII", 0x19, 72 + 80, b"__TEXT", 0x100000000, 0x1000, 0, size, 5, 5, 1, 0)
+    section = struct.pack(
+        "<16s16sQQIIIIIIII",
+        b"__cstring",
+        b"__TEXT",
+        0x100000000 + strings_offset,
+        len(strings),
+        strings_offset,
+        0,
+        0,
+        0,
+        2,
+        0,
+        0,
+        0,
+    )
+    uuid = struct.pack("<II", 0x1B, 24) + MACHO_UUID
+    symtab = struct.pack("<IIIIII", 0x2, 24, symbols_offset, 1, names_offset, len(symbols))
+    image = header + segment + section + uuid + symtab
+    image = image.ljust(strings_offset, b"\0") + strings
+    image = image.ljust(symbols_offset, b"\0") + struct.pack("<IBBHQ", 1, 0x01, 0, 0x0100, 0)
+    return image + symbols
+
+
+def fat_image(thin):
+    """Universal binary with the x86_64 slice at 4 KiB."""
+    header = struct.pack(">II", 0xCAFEBABE, 1) + struct.pack(">iiIII", 0x01000007, 3, 0x1000, len(thin), 12)
+    return header.ljust(0x1000, b"\0") + thin
+
+
+INFO_PLIST = """<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>CFBundleName</key>
+  <string>Dwarf Fortress</string>
+  <key>CFBundleExecutable</key>
+  <string>dwarfort</string>
+</dict>
+</plist>
+"""
+
+
+def elf_image():
+    with tempfile.TemporaryDirectory() as directory:
+        source = Path(directory) / "dwarfort.c"
+        binary = Path(directory) / "dwarfort"
+        source.write_text(ELF_SOURCE)
+        subprocess.run(["gcc", "-O0", "-s", "-Wl,--build-id=sha1", "-o", binary, source], check=True)
+        return binary.read_bytes()
+
+
+output_path.mkdir(parents=True, exist_ok=True)
+(output_path / "dwarfort").write_bytes(elf_image())
+(output_path / "Dwarf Fortress.exe").write_bytes(pe_image())
+bundle_path = output_path / "Dwarf Fortress.app" / "Contents"
+(bundle_path / "MacOS").mkdir(parents=True, exist_ok=True)
+(bundle_path / "Resources" / "data").mkdir(parents=True, exist_ok=True)
+(bundle_path / "Info.plist").write_text(INFO_PLIST)
+(bundle_path / "MacOS" / "dwarfort").write_bytes(fat_image(macho_image()))
+(bundle_path / "Resources" / "data" / "index").write_bytes(b"")
@@ -1,16 +1,21 @@
+use dfint_installer::{
+  cache::cache,
+  constants::*,
+  installer::{Installer, NO_LANGUAGE},
+  progress::Transfers,
+  settings::Settings,
+};
 use eframe::egui::{
-  Align, Button, CentralPanel, ComboBox, Context, FontId, Grid, Image, Layout, Rect, Spinner, TextStyle, TopBottomPanel,
+  Align, Button, CentralPanel, ComboBox, Context, FontId, Grid, Image, Layout, ProgressBar, Rect, Spinner, TextStyle,
+  TopBottomPanel,
 };
 use std::path::PathBuf;
 
 use crate::{
-  constants::*,
-  df_binary::DfBinary,
-  dict_metadata::DictMetadata,
-  hook_metadata::HookMetadata,
   localization::{LOCALE, t},
-  logic::Message,
+  logic::{Message, changes_text, date_text, dict_status, hook_status, os_text, transfers_text},
   thread_pool::ThreadPool,
+  ui_constants::*,
 };
 
 #[derive(PartialEq)]
@@ -25,19 +30,35 @@ pub struct App {
   pub pool: ThreadPool<Message>,
   pub toast: egui_notify::Toasts,
   pub open_file_dialog: Option<egui_file::FileDialog>,
+  pub open_bundle_dialog: Option<egui_file::FileDialog>,
+  pub export_bundle_dialog: Option<egui_file::FileDialog>,
   pub opened_file: Option<PathBuf>,
   pub delete_old_data_show: bool,
   pub delete_hook_show: bool,
+  pub restore_original_show: bool,
+  pub keep_local_show: bool,
+  pub settings_show: bool,
+  pub installations_show: bool,
+  /// Supported DF builds are listed
+  pub compat_show: bool,
   pub on_start: bool,
   pub loading: u8,
+  pub transfers: Transfers,
   pub df_running: bool,
-  pub selected_language: String,
   pub ui_locale: String,
-  pub hook_checksum: u32,
-  pub dict_checksum: u32,
-  pub hook_metadata: HookMetadata,
-  pub dict_metadata: DictMetadata,
-  pub bin: DfBinary,
+  pub installer: Installer,
+  pub settings: Settings,
+  pub new_mirror: String,
+  pub new_scan_root: String,
+  /// Proxy being edited in the settings
+  pub proxy: String,
+  /// Bytes in the download cache, measured when the settings are opened
+  pub cache_size: u64,
+  /// Registered DF installations, read when the list is opened
+  pub installations: Vec<Installer>,
+  /// Registered paths that can't be read, with the reason
+  pub invalid_installations: Vec<(PathBuf, String)>,
+  pub selected_installations: Vec<PathBuf>,
   pub state: State,
 }
 
@@ -47,19 +68,30 @@ impl Default for App {
       pool: ThreadPool::new(),
       toast: egui_notify::Toasts::default().with_anchor(egui_notify::Anchor::BottomRight),
       open_file_dialog: None,
+      open_bundle_dialog: None,
+      export_bundle_dialog: None,
       opened_file: None,
       delete_old_data_show: false,
       delete_hook_show: false,
+      restore_original_show: false,
+      keep_local_show: false,
+      settings_show: false,
+      installations_show: false,
+      compat_show: false,
       on_start: true,
       loading: 0,
+      transfers: Transfers::default(),
       df_running: false,
-      selected_language: "None".to_string(),
       ui_locale: LOCALE.read().current_locale(),
-      hook_checksum: 0,
-      dict_checksum: 0,
-      hook_metadata: HookMetadata::default(),
-      dict_metadata: DictMetadata::default(),
-      bin: DfBinary::default(),
+      installer: Installer::default(),
+      settings: Settings::load(),
+      new_mirror: String::new(),
+      new_scan_root: String::new(),
+      proxy: String::new(),
+      cache_size: 0,
+      installations: vec![],
+      invalid_installations: vec![],
+      selected_installations: vec![],
       state: State::Startup,
     }
   }
@@ -89,11 +121,13 @@ impl eframe::App for App {
       self.on_start();
     }
     // if binary not picked, force to do it
-    if !self.bin.valid && self.open_file_dialog.is_none() && self.state == State::Idle {
+    if !self.installer.bin.valid && self.open_file_dialog.is_none() && self.state == State::Idle {
       self.open_file_dialog = self.file_dialog(None);
     }
     // if file dialog opened
     self.opened_file_dialog(ctx);
+    self.opened_bundle_dialog(ctx);
+    self.opened_export_dialog(ctx);
     // if delete old data dialog opened
     if self.delete_old_data_show {
       self.delete_old_hook_dialog(ctx)
@@ -102,6 +136,26 @@ impl eframe::App for App {
     if self.delete_hook_show {
       self.delete_hook_dialog(ctx)
     }
+    // if restore original dialog opened
+    if self.restore_original_show {
+      self.restore_original_dialog(ctx)
+    }
+    // if keep local changes dialog opened
+    if self.keep_local_show {
+      self.keep_local_dialog(ctx)
+    }
+    // if mirror settings opened
+    if self.settings_show {
+      self.settings_window(ctx)
+    }
+    // if list of installations opened
+    if self.installations_show {
+      self.installations_window(ctx)
+    }
+    // if list of supported builds opened
+    if self.compat_show {
+      self.compat_window(ctx)
+    }
     // show loading on startup
     if self.state != State::Idle {
       CentralPanel::default().show(ctx, |ui| {
@@ -132,7 +186,21 @@ impl eframe::App for App {
           );
           ui.hyperlink_to(t!("Help with translation"), URL_TRANSIFEX);
           ui.label(format!("v{VERSION}"));
+          if self.loading == 0
+            && let Some(date) = self.installer.offline_since()
+          {
+            ui.colored_label(
+              COLOR_UPDATE_AVAILABLE,
+              format!("⚠ {} {}", t!("offline, data from"), date_text(date)),
+            )
+            .on_hover_text(t!("Mirrors unreachable, using saved data"));
+          }
           ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            if ui.small_button("⚙").on_hover_text(t!("Settings")).clicked() {
+              self.settings_show = !self.settings_show;
+              self.cache_size = cache().size();
+              self.proxy = self.settings.proxy.clone().unwrap_or_default();
+            }
             ComboBox::from_id_salt("locale")
               .selected_text(&self.ui_locale)
               .width(50.)
@@ -163,41 +231,83 @@ impl eframe::App for App {
         .striped(true)
         .show(ui, |ui| {
           ui.label(t!("Path"));
-          ui.label(self.bin.to_string());
-          if ui.small_button("🔍").clicked() {
-            self.open_file_dialog = self.file_dialog(Some(self.bin.dir.clone()));
-          };
+          ui.label(self.installer.bin.to_string());
+          ui.horizontal(|ui| {
+            if ui.small_button("🔍").clicked() {
+              self.open_file_dialog = self.file_dialog(Some(self.installer.bin.dir.clone()));
+            };
+            if ui
+              .small_button("🖥")
+              .on_hover_text(t!("Installations"))
+              .clicked()
+            {
+              self.installations_show = !self.installations_show;
+              self.refresh_installations();
+            }
+          });
           ui.end_row();
           ui.label(t!("Version"));
           ui.label(format!(
             "{} | {} | {}",
-            self.bin.version,
-            self.bin.os,
-            match self.bin.steam {
+            self.installer.bin.version,
+            os_text(&self.installer.bin),
+            match self.installer.bin.steam {
               true => "steam",
               false => "non-steam",
             }
           ));
           ui.end_row();
           ui.label(t!("Checksum"));
-          ui.label(format!("{:x}", self.bin.checksum));
+          ui.label(format!("{:x}", self.installer.bin.checksum));
           ui.end_row();
         });
       ui.add_space(20.);
 
       ui.horizontal(|ui| {
         ui.heading(t!("Hook"));
-        // cheksums without lozalization files
-        if self.hook_checksum != NO_HOOK_CHECKSUM || self.dict_checksum != NO_DICT_CHECKSUM {
-          ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+        ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+          // cheksums without lozalization files
+          if self.installer.installed() {
             let button = ui
               .add_sized([20., 20.], Button::new("🗑"))
               .on_hover_text(t!("Delete localization files"));
             if button.clicked() {
               self.delete_hook_show = true
             }
-          });
-        }
+          }
+          if !self.installer.hook_metadata.vec_manifests.is_empty() {
+            let button = ui
+              .add_sized([20., 20.], Button::new("📋"))
+              .on_hover_text(t!("Supported DF builds"));
+            if button.clicked() {
+              self.compat_show = !self.compat_show
+            }
+          }
+          if self.loading == 0 && self.installer.can_export_bundle() {
+            let button = ui
+              .add_sized([20., 20.], Button::new("💾"))
+              .on_hover_text(t!("Save offline bundle"));
+            if button.clicked() {
+              self.export_bundle_dialog = self.export_dialog();
+            }
+          }
+          if self.loading == 0 {
+            let button = ui
+              .add_sized([20., 20.], Button::new("📦"))
+              .on_hover_text(t!("Install from offline bundle"));
+            if button.clicked() {
+              self.open_bundle_dialog = self.bundle_dialog();
+            }
+          }
+          if self.installer.backed_up {
+            let button = ui
+              .add_sized([20., 20.], Button::new("↺"))
+              .on_hover_text(t!("Restore original game files"));
+            if button.clicked() {
+              self.restore_original_show = true
+            }
+          }
+        });
       });
       ui.separator();
 
@@ -208,26 +318,20 @@ impl eframe::App for App {
         .striped(true)
         .show(ui, |ui| {
           ui.label(t!("Version"));
-          ui.label(self.hook_checksum.to_string());
+          ui.label(self.installer.hook_checksum.to_string());
 
-          if self.hook_metadata.manifest.checksum == 0 {
+          if self.installer.hook_metadata.manifest.checksum == 0 {
             ui.label("?");
           } else {
-            ui.label(self.hook_metadata.manifest.checksum.to_string());
+            ui.label(self.installer.hook_metadata.manifest.checksum.to_string());
           }
 
-          let (text, color) = match (
-            self.hook_metadata.manifest.df == self.bin.checksum,
-            self.hook_metadata.manifest.checksum == self.hook_checksum,
-            self.hook_metadata.manifest.checksum == 0,
-            self.hook_metadata.vec_manifests.is_empty(),
-          ) {
-            (_, _, true, true) => (format!("✖ {}", t!("hook data was not loaded")), COLOR_ERROR),
-            (false, _, _, _) => (format!("✖ {}", t!("this DF version is not supported")), COLOR_ERROR),
-            (true, true, _, _) => (format!("✅ {}", t!("up-to-date")), COLOR_UP_TO_DATE),
-            (true, false, _, _) => (format!("⚠ {}", t!("update available")), COLOR_UPDATE_AVAILABLE),
-          };
-          ui.colored_label(color, text);
+          let changes = self.installer.hook_changes();
+          let (text, color) = hook_status(&self.installer);
+          let label = ui.colored_label(color, text);
+          if !changes.is_empty() {
+            label.on_hover_text(changes_text(&changes));
+          }
 
           ui.end_row();
         });
@@ -243,62 +347,71 @@ impl eframe::App for App {
         .striped(true)
         .show(ui, |ui| {
           ComboBox::from_id_salt("languages")
-            .selected_text(&self.selected_language)
+            .selected_text(&self.installer.language)
             .width(140.)
             .show_ui(ui, |ui| {
-              for item in self.dict_metadata.vec_manifests.clone().iter() {
+              for item in self.installer.dict_metadata.vec_manifests.clone().iter() {
                 if ui
                   .selectable_value(
-                    &mut self.selected_language,
+                    &mut self.installer.language,
                     item.language.clone(),
                     item.language.clone(),
                   )
                   .clicked()
-                  && self.selected_language != "None"
+                  && self.installer.language != NO_LANGUAGE
                 {
                   self
+                    .installer
                     .dict_metadata
-                    .pick_language_by_name(self.selected_language.clone())
+                    .pick_language_by_name(self.installer.language.clone())
                 }
               }
             });
-          ui.label(self.dict_checksum.to_string());
-          if self.dict_metadata.manifest.checksum == 0 {
+          ui.label(self.installer.dict_checksum.to_string());
+          if self.installer.dict_metadata.manifest.checksum == 0 {
             ui.label("?");
           } else {
-            ui.label(self.dict_metadata.manifest.checksum.to_string());
+            ui.label(self.installer.dict_metadata.manifest.checksum.to_string());
           }
 
-          let (text, color) = match (
-            self.dict_metadata.manifest.checksum == self.dict_checksum,
-            self.selected_language == "None",
-            self.dict_metadata.vec_manifests.is_empty(),
-          ) {
-            (_, _, true) => (format!("✖ {}", t!("dictionary data was not loaded")), COLOR_ERROR),
-            (true, false, false) => (format!("✅ {}", t!("up-to-date")), COLOR_UP_TO_DATE),
-            (false, false, false) => (format!("⚠ {}", t!("update available")), COLOR_UPDATE_AVAILABLE),
-            (_, true, false) => (format!("⚠ {}", t!("choose language")), COLOR_CHOOSE_LANGUAGE),
-          };
-          ui.colored_label(color, text);
+          let changes = self.installer.dict_changes();
+          let (text, color) = dict_status(&self.installer);
+          let label = ui.colored_label(color, text);
+          if !changes.is_empty() {
+            label.on_hover_text(changes_text(&changes));
+          }
           ui.end_row();
         });
       ui.add_space(20.);
 
-      if (self.hook_metadata.manifest.df == self.bin.checksum
-        && self.hook_metadata.manifest.checksum != self.hook_checksum)
-        || (self.dict_metadata.manifest.checksum != self.dict_checksum && self.selected_language != "None")
-      {
+      if self.installer.update_available() {
         ui.style_mut().text_styles.insert(
           TextStyle::Button,
           FontId::new(20., eframe::epaint::FontFamily::Proportional),
         );
         ui.vertical_centered(|ui| {
           if self.loading > 0 {
-            ui.add(Spinner::new().size(40.));
+            match self.transfers.fraction() {
+              Some(fraction) => {
+                ui.add(
+                  ProgressBar::new(fraction)
+                    .desired_width(400.)
+                    .text(transfers_text(&self.transfers))
+                    .animate(true),
+                );
+              }
+              None => {
+                ui.add(Spinner::new().size(40.));
+              }
+            }
           } else {
             let button = ui.add_sized([130., 40.], Button::new(t!("Update")));
             if button.clicked() {
-              self.update_data();
+              if self.installer.local_changes().is_empty() {
+                self.update_data();
+              } else {
+                self.keep_local_show = true;
+              }
             }
           }
         });
@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::constants::{PATH_BACKUP, VERSION};
+
+/// Number of most recent snapshots kept in addition to the ones holding original files.
+const KEEP_SNAPSHOTS: usize = 3;
+const INDEX_FILE: &str = "index.json";
+const FILES_DIR: &str = "files";
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+  created: String,
+  installer_version: String,
+  files: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+  /// Path relative to the DF directory
+  path: PathBuf,
+  /// Whether the file existed before the install, if not restoring means deleting it
+  existed: bool,
+}
+
+/// Copies of the files in a DF directory taken before the installer overwrote them.
+///
+/// Every install creates a snapshot in `dfint-backup/<timestamp>`. The first snapshot a file appears in
+/// holds its original state, before the installer ever touched it.
+pub struct Backup {
+  df_dir: PathBuf,
+}
+
+impl Backup {
+  pub fn new(df_dir: &Path) -> Self {
+    Self {
+      df_dir: df_dir.to_path_buf(),
+    }
+  }
+
+  fn root(&self) -> PathBuf {
+    self.df_dir.join(PATH_BACKUP)
+  }
+
+  /// Save the current state of `targets` before they are overwritten.
+  pub fn snapshot(&self, targets: &[PathBuf]) -> Result<()> {
+    std::fs::create_dir_all(self.root())?;
+    let dir = tempfile::Builder::new()
+      .prefix(
+        &chrono::Local::now()
+          .format("%Y%m%d-%H%M%S%.6f-")
+          .to_string(),
+      )
+      .rand_bytes(4)
+      .tempdir_in(self.root())
+      .context("Unable to create backup directory")?;
+
+    let mut files = vec![];
+    for target in targets {
+      let path = target
+        .strip_prefix(&self.df_dir)
+        .with_context(|| format!("{} is outside of the DF directory", target.display()))?
+        .to_path_buf();
+      let existed = target.is_file();
+      if existed {
+        let copy = dir.path().join(FILES_DIR).join(&path);
+        std::fs::create_dir_all(copy.parent().expect("backup path has a parent"))?;
+        std::fs::copy(target, &copy).with_context(|| format!("Unable to back up {}", target.display()))?;
+      }
+      files.push(Entry { path, existed });
+    }
+
+    let snapshot = Snapshot {
+      created: chrono::Local::now().to_rfc3339(),
+      installer_version: VERSION.to_string(),
+      files,
+    };
+    std::fs::write(dir.path().join(INDEX_FILE), serde_json::to_string_pretty(&snapshot)?)?;
+    let _ = dir.keep();
+    self.prune();
+    Ok(())
+  }
+
+  /// Snapshots ordered from the oldest to the newest.
+  fn snapshots(&self) -> Vec<(PathBuf, Snapshot)> {
+    let Ok(entries) = std::fs::read_dir(self.root()) else {
+      return vec![];
+    };
+    let mut dirs: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    dirs.sort();
+    dirs
+      .into_iter()
+      .filter_map(|dir| {
+        let content = std::fs::read_to_string(dir.join(INDEX_FILE)).ok()?;
+        let snapshot: Snapshot = serde_json::from_str(&content).ok()?;
+        Some((dir, snapshot))
+      })
+      .collect()
+  }
+
+  /// The first recorded state of every file together with the snapshot directory it's stored in.
+  fn originals(snapshots: &[(PathBuf, Snapshot)]) -> BTreeMap<&Path, (&Path, bool)> {
+    let mut originals = BTreeMap::new();
+    for (dir, snapshot) in snapshots {
+      for entry in &snapshot.files {
+        originals
+          .entry(entry.path.as_path())
+          .or_insert((dir.as_path(), entry.existed));
+      }
+    }
+    originals
+  }
+
+  fn prune(&self) {
+    let snapshots = self.snapshots();
+    let keep: BTreeSet<&Path> = Self::originals(&snapshots)
+      .values()
+      .map(|(dir, _)| *dir)
+      .chain(
+        snapshots
+          .iter()
+          .rev()
+          .take(KEEP_SNAPSHOTS)
+          .map(|(dir, _)| dir.as_path()),
+      )
+      .collect();
+    for (dir, _) in &snapshots {
+      if !keep.contains(dir.as_path()) {
+        let _ = std::fs::remove_dir_all(dir);
+      }
+    }
+  }
+
+  pub fn has_original(&self) -> bool {
+    !self.snapshots().is_empty()
+  }
+
+  /// Put every file back into the state it had before the first install and drop the backups.
+  pub fn restore_original(&self) -> Result<()> {
+    let snapshots = self.snapshots();
+    for (path, (dir, existed)) in Self::originals(&snapshots) {
+      self.restore_entry(dir, path, existed)?;
+    }
+    std::fs::remove_dir_all(self.root())?;
+    Ok(())
+  }
+
+  /// Put back the original state of a single file, `false` if it was never backed up.
+  pub fn restore(&self, path: &Path) -> Result<bool> {
+    let snapshots = self.snapshots();
+    match Self::originals(&snapshots).get(path) {
+      Some((dir, existed)) => {
+        self.restore_entry(dir, path, *existed)?;
+        Ok(true)
+      }
+      None => Ok(false),
+    }
+  }
+
+  fn restore_entry(&self, dir: &Path, path: &Path, existed: bool) -> Result<()> {
+    let target = self.df_dir.join(path);
+    if existed {
+      if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+      }
+      std::fs::copy(dir.join(FILES_DIR).join(path), &target)
+        .with_context(|| format!("Unable to restore {}", target.display()))?;
+    } else if target.exists() {
+      std::fs::remove_file(&target).with_context(|| format!("Unable to delete {}", target.display()))?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn restore_original() {
+    let dir = tempfile::tempdir().unwrap();
+    let font = dir.path().join("data/art/font.png");
+    let dfhooks = dir.path().join("dfhooks.dll");
+    let dict = dir.path().join("dfint-data/dictionary.csv");
+    std::fs::create_dir_all(font.parent().unwrap()).unwrap();
+    std::fs::create_dir_all(dict.parent().unwrap()).unwrap();
+    std::fs::write(&font, "original font").unwrap();
+    std::fs::write(&dfhooks, "third-party dfhooks").unwrap();
+
+    let backup = Backup::new(dir.path());
+    assert!(!backup.has_original());
+    for version in 1..=5 {
+      let targets = [font.clone(), dfhooks.clone(), dict.clone()];
+      backup.snapshot(&targets).unwrap();
+      for target in &targets {
+        std::fs::write(target, format!("installed {version}")).unwrap();
+      }
+    }
+    assert!(backup.has_original());
+    assert_eq!(backup.snapshots().len(), KEEP_SNAPSHOTS + 1);
+
+    backup.restore_original().unwrap();
+    assert_eq!(std::fs::read_to_string(&font).unwrap(), "original font");
+    assert_eq!(std::fs::read_to_string(&dfhooks).unwrap(), "third-party dfhooks");
+    assert!(!dict.exists());
+    assert!(!dir.path().join(PATH_BACKUP).exists());
+  }
+
+  #[test]
+  fn originals_across_snapshots() {
+    let dir = tempfile::tempdir().unwrap();
+    let lib = dir.path().join("lib.so");
+    let csv = dir.path().join("dictionary.csv");
+    std::fs::write(&csv, "user csv").unwrap();
+
+    let backup = Backup::new(dir.path());
+    backup.snapshot(std::slice::from_ref(&lib)).unwrap();
+    std::fs::write(&lib, "hook").unwrap();
+    backup.snapshot(&[lib.clone(), csv.clone()]).unwrap();
+    std::fs::write(&lib, "hook 2").unwrap();
+    std::fs::write(&csv, "dict").unwrap();
+
+    assert!(backup.restore(Path::new("dictionary.csv")).unwrap());
+    assert_eq!(std::fs::read_to_string(&csv).unwrap(), "user csv");
+    assert!(!backup.restore(Path::new("other.txt")).unwrap());
+
+    backup.restore_original().unwrap();
+    assert!(!lib.exists());
+    assert_eq!(std::fs::read_to_string(&csv).unwrap(), "user csv");
+  }
+}
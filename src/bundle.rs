@@ -0,0 +1,343 @@
+use anyhow::{Context, Result, anyhow};
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+use crate::{
+  cache::cache,
+  constants::{MANIFEST_PUBLIC_KEY, SIGNATURE_EXTENSION, URL_DICT_MANIFEST, URL_HOOK_MANIFEST, VERSION},
+  dict_metadata::{DictMetadata, Manifest as DictManifest},
+  fetch::{Download, download_to_file, fetch_bytes_from},
+  hook_metadata::{HookMetadata, Manifest as HookManifest},
+  mirrors::{Mirrors, mirrors},
+  progress::Reporter,
+  signature,
+};
+
+const CONTENTS_FILE: &str = "bundle.json";
+
+/// What an exported bundle was made for, stored in `bundle.json` next to the manifests.
+///
+/// The manifests are kept as signed, with the entries of all DF versions and languages, while the
+/// bundle only has the files of one hook and one dictionary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contents {
+  pub df: u32,
+  pub language: String,
+  pub created: String,
+  pub installer_version: String,
+  pub files: Vec<String>,
+}
+
+/// Offline copy of the data: a zip or tar (optionally gzipped) archive with the layout of a mirror,
+/// the signed manifests under `update-data/metadata` and the files they reference.
+///
+/// The archive is extracted to a temporary directory which is used as the only mirror, so installing
+/// from a bundle goes through the same signature and checksum checks as a download.
+pub struct Bundle {
+  path: PathBuf,
+  contents: Option<Contents>,
+  // keeps the extracted files until the bundle is dropped
+  _dir: TempDir,
+  mirrors: Mirrors,
+}
+
+impl Bundle {
+  pub fn open(path: &Path) -> Result<Self> {
+    let dir = tempfile::Builder::new().prefix("dfint-bundle-").tempdir()?;
+    extract(path, dir.path()).with_context(|| format!("Unable to extract bundle {}", path.display()))?;
+    let root = find_root(dir.path())
+      .with_context(|| format!("{} is not a localization bundle, it has no manifests", path.display()))?;
+    let contents = std::fs::read_to_string(root.join(CONTENTS_FILE))
+      .ok()
+      .and_then(|content| serde_json::from_str(&content).ok());
+    Ok(Self {
+      path: path.to_path_buf(),
+      contents,
+      mirrors: Mirrors::new([format!("file://{}", root.display())]),
+      _dir: dir,
+    })
+  }
+
+  /// Open the bundle and read its manifests, picking the entries for `df_checksum` and `language`.
+  pub async fn load(
+    path: PathBuf,
+    df_checksum: u32,
+    language: Option<String>,
+  ) -> Result<(Self, HookMetadata, DictMetadata)> {
+    let bundle = Self::open(&path)?;
+    let hook_metadata = HookMetadata::from_mirrors(&bundle.mirrors, URL_HOOK_MANIFEST, Some(df_checksum)).await?;
+    let dict_metadata = DictMetadata::from_mirrors(&bundle.mirrors, URL_DICT_MANIFEST, language).await?;
+    Ok((bundle, hook_metadata, dict_metadata))
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn mirrors(&self) -> &Mirrors {
+    &self.mirrors
+  }
+
+  /// `None` for bundles which weren't exported by the installer.
+  pub fn contents(&self) -> Option<&Contents> {
+    self.contents.as_ref()
+  }
+
+  /// Download the files of `hook` and `dict` together with the signed manifests and pack them into
+  /// an archive at `path`, a zip or a tar depending on the extension.
+  pub async fn export(path: PathBuf, hook: HookManifest, dict: DictManifest, progress: Reporter) -> Result<Contents> {
+    let mirrors = mirrors();
+    let dir = tempfile::Builder::new().prefix("dfint-bundle-").tempdir()?;
+    let local = |url: &str| dir.path().join(url.trim_start_matches('/'));
+    let mut files = vec![];
+
+    for manifest in [URL_HOOK_MANIFEST, URL_DICT_MANIFEST] {
+      let data = fetch_bytes_from(&mirrors, manifest)?;
+      let signature_url = format!("{manifest}{SIGNATURE_EXTENSION}");
+      let signature = fetch_bytes_from(&mirrors, &signature_url)?;
+      signature::verify(&data, &signature, &MANIFEST_PUBLIC_KEY).with_context(|| format!("Rejected {manifest}"))?;
+      for (url, content) in [(manifest, data), (signature_url.as_str(), signature)] {
+        std::fs::create_dir_all(local(url).parent().expect("manifest path has a parent"))?;
+        std::fs::write(local(url), content)?;
+        files.push(url.to_string());
+      }
+    }
+
+    let items: Vec<Download> = hook
+      .files()
+      .into_iter()
+      .map(|(field, url)| (hook.sha256.get(field).cloned(), url))
+      .chain(
+        dict
+          .files()
+          .into_iter()
+          .map(|(field, url)| (dict.sha256.get(field).cloned(), url)),
+      )
+      .map(|(sha256, url)| Download {
+        file: local(&url),
+        url,
+        sha256,
+      })
+      .collect();
+    let partial_dir = dir.path().join(".partial");
+    for item in &items {
+      std::fs::create_dir_all(item.file.parent().expect("download path has a parent"))?;
+      download_to_file(&mirrors, Some(&cache()), item, &item.file, &partial_dir, &progress).await?;
+      files.push(item.url.clone());
+    }
+
+    let contents = Contents {
+      df: hook.df,
+      language: dict.language,
+      created: chrono::Local::now().to_rfc3339(),
+      installer_version: VERSION.to_string(),
+      files,
+    };
+    std::fs::write(dir.path().join(CONTENTS_FILE), serde_json::to_string_pretty(&contents)?)?;
+
+    let mut names = contents.files.clone();
+    names.push(CONTENTS_FILE.to_string());
+    pack(dir.path(), &names, &path).with_context(|| format!("Unable to write bundle {}", path.display()))?;
+    Ok(contents)
+  }
+}
+
+/// Write `names` from `dir` into an archive, the format is picked by the extension of `path`.
+fn pack(dir: &Path, names: &[String], path: &Path) -> Result<()> {
+  let parent = path
+    .parent()
+    .filter(|parent| !parent.as_os_str().is_empty())
+    .unwrap_or(Path::new("."));
+  // written next to the target and moved into place, so a failed export leaves no broken archive
+  let mut file = tempfile::NamedTempFile::new_in(parent)?;
+  let name = path.to_string_lossy().to_lowercase();
+  let names = names.iter().map(|name| name.trim_start_matches('/'));
+  if name.ends_with(".zip") {
+    let mut writer = zip::ZipWriter::new(file.as_file_mut());
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for name in names {
+      writer.start_file(name, options)?;
+      writer.write_all(&std::fs::read(dir.join(name))?)?;
+    }
+    writer.finish()?;
+  } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+    let encoder = flate2::write::GzEncoder::new(file.as_file_mut(), flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for name in names {
+      builder.append_path_with_name(dir.join(name), name)?;
+    }
+    builder.into_inner()?.finish()?;
+  } else if name.ends_with(".tar") {
+    let mut builder = tar::Builder::new(file.as_file_mut());
+    for name in names {
+      builder.append_path_with_name(dir.join(name), name)?;
+    }
+    builder.finish()?;
+  } else {
+    return Err(anyhow!("Unknown bundle format, use .zip, .tar or .tar.gz"));
+  }
+  file.persist(path)?;
+  Ok(())
+}
+
+fn extract(path: &Path, dir: &Path) -> Result<()> {
+  let mut file = File::open(path)?;
+  let mut magic = [0u8; 4];
+  let read = file.read(&mut magic)?;
+  file.rewind()?;
+  match &magic[..read] {
+    // both extractors refuse entries pointing outside of `dir`
+    [b'P', b'K', 3, 4] => zip::ZipArchive::new(file)?.extract(dir)?,
+    [0x1f, 0x8b, ..] => tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(dir)?,
+    _ => tar::Archive::new(file).unpack(dir)?,
+  }
+  Ok(())
+}
+
+/// Directory with the mirror layout, either the archive root or its only top-level directory.
+fn find_root(dir: &Path) -> Option<PathBuf> {
+  let has_manifest = |root: &Path| {
+    root
+      .join(URL_HOOK_MANIFEST.trim_start_matches('/'))
+      .is_file()
+  };
+  if has_manifest(dir) {
+    return Some(dir.to_path_buf());
+  }
+  let mut entries = std::fs::read_dir(dir).ok()?.flatten();
+  let (Some(entry), None) = (entries.next(), entries.next()) else {
+    return None;
+  };
+  let root = entry.path();
+  has_manifest(&root).then_some(root)
+}
+
+/// Whether the file looks like a bundle by its name.
+pub fn is_bundle(path: &Path) -> bool {
+  let name = path
+    .file_name()
+    .map(|name| name.to_string_lossy().to_lowercase())
+    .unwrap_or_default();
+  [".zip", ".tar", ".tar.gz", ".tgz"]
+    .iter()
+    .any(|extension| name.ends_with(extension))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const FILES: [(&str, &str); 2] = [
+    ("update-data/metadata/hook_v3.json", "[]"),
+    ("update-data/hook/lib.so", "hook"),
+  ];
+
+  fn zip_bundle(path: &Path, prefix: &str) {
+    let mut writer = zip::ZipWriter::new(File::create(path).unwrap());
+    for (name, content) in FILES {
+      writer
+        .start_file(format!("{prefix}{name}"), zip::write::SimpleFileOptions::default())
+        .unwrap();
+      writer.write_all(content.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap();
+  }
+
+  fn tar_gz_bundle(path: &Path) {
+    let encoder = flate2::write::GzEncoder::new(File::create(path).unwrap(), flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for (name, content) in FILES {
+      let mut header = tar::Header::new_gnu();
+      header.set_size(content.len() as u64);
+      header.set_mode(0o644);
+      header.set_cksum();
+      builder
+        .append_data(&mut header, name, content.as_bytes())
+        .unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap();
+  }
+
+  #[test]
+  fn open_archives() {
+    let dir = tempfile::tempdir().unwrap();
+    let zip = dir.path().join("bundle.zip");
+    let nested = dir.path().join("nested.zip");
+    let tar_gz = dir.path().join("bundle.tar.gz");
+    zip_bundle(&zip, "");
+    zip_bundle(&nested, "dfint-bundle/");
+    tar_gz_bundle(&tar_gz);
+
+    for path in [zip, nested, tar_gz] {
+      assert!(is_bundle(&path));
+      let bundle = Bundle::open(&path).unwrap();
+      assert_eq!(
+        fetch_bytes_from(bundle.mirrors(), "/update-data/hook/lib.so").unwrap(),
+        b"hook"
+      );
+    }
+  }
+
+  #[test]
+  fn not_a_bundle() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bundle.zip");
+    std::fs::write(&path, "garbage").unwrap();
+    assert!(Bundle::open(&path).is_err());
+
+    let empty = dir.path().join("empty.tar");
+    tar::Builder::new(File::create(&empty).unwrap())
+      .into_inner()
+      .unwrap();
+    assert!(Bundle::open(&empty).is_err());
+  }
+
+  #[test]
+  fn pack_and_open() {
+    let dir = tempfile::tempdir().unwrap();
+    let names: Vec<String> = FILES.iter().map(|(name, _)| format!("/{name}")).collect();
+    for (name, content) in FILES {
+      std::fs::create_dir_all(dir.path().join(name).parent().unwrap()).unwrap();
+      std::fs::write(dir.path().join(name), content).unwrap();
+    }
+    let contents = Contents {
+      df: 1,
+      language: "Russian".to_string(),
+      created: String::new(),
+      installer_version: VERSION.to_string(),
+      files: names.clone(),
+    };
+    std::fs::write(
+      dir.path().join(CONTENTS_FILE),
+      serde_json::to_string(&contents).unwrap(),
+    )
+    .unwrap();
+    let mut with_contents = names.clone();
+    with_contents.push(CONTENTS_FILE.to_string());
+
+    let out = tempfile::tempdir().unwrap();
+    for name in ["bundle.zip", "bundle.tar", "bundle.tgz"] {
+      let path = out.path().join(name);
+      pack(dir.path(), &with_contents, &path).unwrap();
+      let bundle = Bundle::open(&path).unwrap();
+      assert_eq!(bundle.contents(), Some(&contents));
+      assert_eq!(
+        fetch_bytes_from(bundle.mirrors(), "/update-data/hook/lib.so").unwrap(),
+        b"hook"
+      );
+    }
+    assert!(pack(dir.path(), &names, &out.path().join("bundle.rar")).is_err());
+    assert!(!out.path().join("bundle.rar").exists());
+  }
+
+  #[test]
+  fn unsigned_manifests_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bundle.zip");
+    zip_bundle(&path, "");
+    let result = futures::executor::block_on(Bundle::load(path, 0, None));
+    assert!(result.is_err());
+  }
+}
@@ -0,0 +1,257 @@
+use anyhow::Result;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::SystemTime;
+
+use crate::utils::sha256_hex;
+
+/// Default size limit of the download cache in MB.
+pub const DEFAULT_CACHE_LIMIT: u64 = 512;
+const MIB: u64 = 1024 * 1024;
+
+static CACHE: LazyLock<RwLock<Arc<Cache>>> =
+  LazyLock::new(|| RwLock::new(Arc::new(Cache::new(default_dir(), DEFAULT_CACHE_LIMIT * MIB))));
+
+/// Download cache shared by all game installations on this machine.
+pub fn cache() -> Arc<Cache> {
+  CACHE.read().unwrap_or_else(|err| err.into_inner()).clone()
+}
+
+/// Replace the size limit of the shared cache, `0` disables it.
+pub fn set_cache_limit(megabytes: u64) {
+  *CACHE.write().unwrap_or_else(|err| err.into_inner()) = Arc::new(Cache::new(default_dir(), megabytes * MIB));
+}
+
+/// `dfint-installer/downloads` in the user's cache directory, `None` if the platform has none.
+fn default_dir() -> Option<PathBuf> {
+  dirs::cache_dir().map(|dir| dir.join("dfint-installer").join("downloads"))
+}
+
+/// Store of downloaded files, named by their sha256 if the manifest has one.
+///
+/// Files with a sha256 known in advance are checked against it when they are read. Files
+/// without one are stored by URL together with the aggregate checksum of their manifest, so
+/// an entry goes out of use when the manifest changes; the caller checks the checksum. Least
+/// recently used entries are removed when the cache grows over its limit.
+pub struct Cache {
+  dir: Option<PathBuf>,
+  limit: u64,
+}
+
+impl Cache {
+  pub fn new(dir: Option<PathBuf>, limit: u64) -> Self {
+    Self { dir, limit }
+  }
+
+  pub fn dir(&self) -> Option<&Path> {
+    self.dir.as_deref()
+  }
+
+  /// Size limit in bytes.
+  pub fn limit(&self) -> u64 {
+    self.limit
+  }
+
+  fn entry(&self, sha256: &str) -> Option<PathBuf> {
+    if self.limit == 0 {
+      return None;
+    }
+    Some(self.dir.as_ref()?.join(sha256.to_lowercase()))
+  }
+
+  fn url_entry(&self, url: &str, checksum: u32) -> Option<PathBuf> {
+    let key = sha256_hex(format!("{checksum:x} {url}").as_bytes());
+    self.entry(&format!("{key}.url"))
+  }
+
+  /// Copy the cached file with `sha256` to `dest`, `false` if there is none.
+  pub fn get(&self, sha256: &str, dest: &Path) -> bool {
+    let Some(entry) = self.entry(sha256) else {
+      return false;
+    };
+    let Ok(data) = fs::read(&entry) else {
+      return false;
+    };
+    if !sha256_hex(&data).eq_ignore_ascii_case(sha256) {
+      let _ = fs::remove_file(&entry);
+      return false;
+    }
+    // mark as recently used
+    if let Ok(file) = File::options().write(true).open(&entry) {
+      let _ = file.set_modified(SystemTime::now());
+    }
+    fs::write(dest, data).is_ok()
+  }
+
+  /// Copy the cached file of `url` in the manifest with `checksum` to `dest`, `false` if there is none.
+  /// The content isn't checked here.
+  pub fn get_by_url(&self, url: &str, checksum: u32, dest: &Path) -> bool {
+    let Some(entry) = self.url_entry(url, checksum) else {
+      return false;
+    };
+    if fs::copy(&entry, dest).is_err() {
+      return false;
+    }
+    // mark as recently used
+    if let Ok(file) = File::options().write(true).open(&entry) {
+      let _ = file.set_modified(SystemTime::now());
+    }
+    true
+  }
+
+  /// Store a copy of `file`, which is known to have `sha256`.
+  pub fn put(&self, sha256: &str, file: &Path) -> Result<()> {
+    self.store(self.entry(sha256), file)
+  }
+
+  /// Store a copy of `file` downloaded from `url`, which matched the manifest `checksum`.
+  pub fn put_by_url(&self, url: &str, checksum: u32, file: &Path) -> Result<()> {
+    self.store(self.url_entry(url, checksum), file)
+  }
+
+  /// Drop the entry of `url`, for a file that didn't match the manifest checksum.
+  pub fn remove_by_url(&self, url: &str, checksum: u32) {
+    if let Some(entry) = self.url_entry(url, checksum) {
+      let _ = fs::remove_file(entry);
+    }
+  }
+
+  fn store(&self, entry: Option<PathBuf>, file: &Path) -> Result<()> {
+    let Some(entry) = entry else {
+      return Ok(());
+    };
+    if entry.exists() {
+      return Ok(());
+    }
+    let dir = entry.parent().expect("cache entries are in the cache dir");
+    fs::create_dir_all(dir)?;
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    fs::copy(file, temp.path())?;
+    temp.persist(&entry)?;
+    self.trim()
+  }
+
+  /// Cached files with their size and last use, oldest first.
+  fn entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+    let Some(dir) = &self.dir else {
+      return vec![];
+    };
+    let mut entries: Vec<_> = fs::read_dir(dir)
+      .into_iter()
+      .flatten()
+      .flatten()
+      .filter_map(|entry| {
+        let metadata = entry.metadata().ok()?;
+        metadata.is_file().then(|| {
+          (
+            entry.path(),
+            metadata.len(),
+            metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+          )
+        })
+      })
+      .collect();
+    entries.sort_by_key(|(_, _, used)| *used);
+    entries
+  }
+
+  /// Bytes used by the cached files.
+  pub fn size(&self) -> u64 {
+    self.entries().iter().map(|(_, size, _)| size).sum()
+  }
+
+  /// Remove the least recently used files until the cache fits its limit.
+  fn trim(&self) -> Result<()> {
+    let entries = self.entries();
+    let mut size: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    for (path, len, _) in entries {
+      if size <= self.limit {
+        break;
+      }
+      fs::remove_file(path)?;
+      size -= len;
+    }
+    Ok(())
+  }
+
+  /// Remove all cached files, returns the bytes freed.
+  pub fn clear(&self) -> Result<u64> {
+    let mut freed = 0;
+    for (path, len, _) in self.entries() {
+      fs::remove_file(path)?;
+      freed += len;
+    }
+    Ok(freed)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+
+  #[test]
+  fn get_and_put() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = Cache::new(Some(dir.path().join("cache")), 10);
+    let file = dir.path().join("file");
+    let dest = dir.path().join("dest");
+    fs::write(&file, "data").unwrap();
+    let sha256 = sha256_hex(b"data");
+
+    assert!(!cache.get(&sha256, &dest));
+    cache.put(&sha256, &file).unwrap();
+    assert!(cache.get(&sha256.to_uppercase(), &dest));
+    assert_eq!(fs::read(&dest).unwrap(), b"data");
+
+    // corrupted entries are dropped
+    fs::write(dir.path().join("cache").join(&sha256), "other").unwrap();
+    assert!(!cache.get(&sha256, &dest));
+    assert_eq!(cache.size(), 0);
+
+    let disabled = Cache::new(Some(dir.path().join("cache")), 0);
+    disabled.put(&sha256, &file).unwrap();
+    assert!(!disabled.get(&sha256, &dest));
+  }
+
+  #[test]
+  fn by_url() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = Cache::new(Some(dir.path().join("cache")), 10);
+    let file = dir.path().join("file");
+    let dest = dir.path().join("dest");
+    fs::write(&file, "data").unwrap();
+
+    cache.put_by_url("/data/file", 1, &file).unwrap();
+    assert!(cache.get_by_url("/data/file", 1, &dest));
+    assert_eq!(fs::read(&dest).unwrap(), b"data");
+    // another manifest
+    assert!(!cache.get_by_url("/data/file", 2, &dest));
+    cache.remove_by_url("/data/file", 1);
+    assert!(!cache.get_by_url("/data/file", 1, &dest));
+  }
+
+  #[test]
+  fn trim_and_clear() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = Cache::new(Some(dir.path().join("cache")), 11);
+    let file = dir.path().join("file");
+    let mut hashes = vec![];
+    for data in ["first", "second", "third"] {
+      fs::write(&file, data).unwrap();
+      let sha256 = sha256_hex(data.as_bytes());
+      cache.put(&sha256, &file).unwrap();
+      hashes.push(sha256);
+      std::thread::sleep(Duration::from_millis(20));
+    }
+
+    // the oldest entry made room for "third"
+    assert_eq!(cache.size(), 11);
+    assert!(!cache.get(&hashes[0], &dir.path().join("dest")));
+    assert!(cache.get(&hashes[1], &dir.path().join("dest")));
+
+    assert_eq!(cache.clear().unwrap(), 11);
+    assert_eq!(cache.size(), 0);
+  }
+}
@@ -1,16 +1,10 @@
-use eframe::egui::Color32;
-
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
-pub const GITHUB_ICON: eframe::egui::widgets::ImageSource<'static> =
-  eframe::egui::include_image!("../assets/github.png");
-pub const TRANSIFEX_ICON: eframe::egui::widgets::ImageSource<'static> =
-  eframe::egui::include_image!("../assets/transifex.png");
-pub const APP_ICON: &'static [u8; 1980] = include_bytes!("../assets/df.png");
 pub const ORIGINAL_FONT: &'static [u8; 1568] = include_bytes!("../assets/original_font.png");
 
 pub const PATH_CACHE_FILE: &'static str = "./dfint-installer.cache";
 pub const PATH_ERROR_FILE: &'static str = "./dfint-error.log";
+pub const PATH_SETTINGS_FILE: &'static str = "./dfint-installer.settings";
 
 pub const BASE_URL: [&'static str; 2] = [
   "https://dfint.github.io",
@@ -19,21 +13,25 @@ pub const BASE_URL: [&'static str; 2] = [
 
 pub const URL_HOOK_MANIFEST: &'static str = "/update-data/metadata/hook_v3.json";
 pub const URL_DICT_MANIFEST: &'static str = "/update-data/metadata/dict_v3.json";
+pub const SIGNATURE_EXTENSION: &'static str = ".sig";
+/// ed25519 public key the manifests are signed with, each manifest has a detached signature
+/// at the same path with `SIGNATURE_EXTENSION` appended
+pub const MANIFEST_PUBLIC_KEY: [u8; 32] = [
+  0xce, 0xb8, 0x93, 0x43, 0xed, 0xa1, 0xfb, 0xac, 0xbf, 0xa4, 0xfc, 0x2c, 0x0c, 0x36, 0xd2, 0xac, 0x59, 0xab, 0x4f,
+  0x97, 0xa4, 0x8c, 0xc1, 0x55, 0x9f, 0xd0, 0xf5, 0xe5, 0xdf, 0x8b, 0xc8, 0x12,
+];
 pub const URL_BUGS: &'static str = "https://github.com/dfint/installer/issues";
 pub const URL_TRANSIFEX: &'static str =
   "https://explore.transifex.com/dwarf-fortress-translation/dwarf-fortress-steam/";
 
 pub const PATH_DATA: &'static str = "dfint-data";
+pub const PATH_BACKUP: &'static str = "dfint-backup";
 pub const PATH_CONFIG: &'static str = "dfint-data/config.toml";
 pub const PATH_OFFSETS: &'static str = "dfint-data/offsets.toml";
 pub const PATH_DICT: &'static str = "dfint-data/dictionary.csv";
 pub const PATH_FONT: &'static str = "data/art/curses_640x300.png";
 pub const PATH_ENCODING: &'static str = "dfint-data/encoding.toml";
-
-pub const COLOR_UP_TO_DATE: Color32 = Color32::from_rgb(67, 160, 71);
-pub const COLOR_UPDATE_AVAILABLE: Color32 = Color32::from_rgb(230, 184, 0);
-pub const COLOR_CHOOSE_LANGUAGE: Color32 = Color32::from_rgb(230, 184, 0);
-pub const COLOR_ERROR: Color32 = Color32::from_rgb(211, 47, 47);
+pub const PATH_RECEIPT: &'static str = "dfint-data/receipt.json";
 
 pub const NO_HOOK_CHECKSUM: u32 = 4282505490;
 pub const NO_DICT_CHECKSUM: u32 = 1591420153;
@@ -1,16 +1,79 @@
-use anyhow::Result;
-use exe::{PE, VecPE};
-
 use regex::bytes::{Captures, Regex};
-use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::{discovery::EXECUTABLES, identify::Identity};
 
 const MAX_BETA: u32 = 10_000;
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+static VERSION_PATTERN: LazyLock<Regex> =
+  LazyLock::new(|| Regex::new(r"\x00((\d+)\.(\d+)(-([^\d]+)(\d*))?)\x00").unwrap());
+/// Name of the executable in the `Info.plist` of an app bundle.
+static BUNDLE_EXECUTABLE: LazyLock<regex::Regex> =
+  LazyLock::new(|| regex::Regex::new(r"<key>CFBundleExecutable</key>\s*<string>([^<]+)</string>").unwrap());
+
+/// Why a file can't be used as the DF executable.
+#[derive(Debug)]
+pub enum DfBinaryError {
+  NotFound,
+  NotDfExecutable,
+  PermissionDenied,
+  Unreadable(std::io::Error),
+  UnsupportedPe32,
+  CorruptHeader(String),
+}
+
+impl std::fmt::Display for DfBinaryError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      DfBinaryError::NotFound => std::write!(f, "the file doesn't exist, pick the executable in the DF folder"),
+      DfBinaryError::NotDfExecutable => std::write!(
+        f,
+        "not a Dwarf Fortress executable, pick \"Dwarf Fortress.exe\", \"dwarfort\" or \"Dwarf Fortress.app\" in the DF folder"
+      ),
+      DfBinaryError::PermissionDenied => std::write!(
+        f,
+        "no permission to read the file, check its permissions or run the installer as the user who installed DF"
+      ),
+      DfBinaryError::Unreadable(err) => {
+        std::write!(f, "unable to read the file ({err}), check that the drive is available")
+      }
+      DfBinaryError::UnsupportedPe32 => {
+        std::write!(f, "32-bit Windows builds are not supported, install the 64-bit DF")
+      }
+      DfBinaryError::CorruptHeader(detail) => std::write!(
+        f,
+        "the executable is damaged ({detail}), verify the game files in Steam or reinstall DF"
+      ),
+    }
+  }
+}
+
+impl std::error::Error for DfBinaryError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      DfBinaryError::Unreadable(err) => Some(err),
+      _ => None,
+    }
+  }
+}
+
+impl From<std::io::Error> for DfBinaryError {
+  fn from(err: std::io::Error) -> Self {
+    match err.kind() {
+      std::io::ErrorKind::NotFound => DfBinaryError::NotFound,
+      std::io::ErrorKind::PermissionDenied => DfBinaryError::PermissionDenied,
+      _ => DfBinaryError::Unreadable(err),
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OS {
   Linux = 0,
   Windows = 1,
+  MacOS = 2,
 }
 
 impl std::fmt::Display for OS {
@@ -18,17 +81,30 @@ impl std::fmt::Display for OS {
     match self {
       OS::Linux => std::write!(f, "🐧 Linux"),
       OS::Windows => std::write!(f, " Windows"),
+      OS::MacOS => std::write!(f, "🍏 macOS"),
     }
   }
 }
 
+/// Compatibility layer a Windows build runs in on another OS.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compat {
+  Proton,
+  Wine,
+}
+
+#[derive(Clone)]
 pub struct DfBinary {
   pub path: PathBuf,
   pub dir: PathBuf,
   pub checksum: u32,
   pub os: OS,
   pub version: String,
+  pub build_id: Option<String>,
   pub steam: bool,
+  /// Set for a Windows build on a Linux or macOS host, the Windows hook is used all the same
+  pub compat: Option<Compat>,
   pub valid: bool,
   pub lib_path: PathBuf,
   pub dfhooks_path: PathBuf,
@@ -42,7 +118,9 @@ impl Default for DfBinary {
       checksum: 0,
       os: OS::Windows,
       version: String::from("Unknown"),
+      build_id: None,
       steam: false,
+      compat: None,
       valid: false,
       lib_path: std::env::current_dir().expect("Unable to locate current dir"),
       dfhooks_path: std::env::current_dir().expect("Unable to locate current dir"),
@@ -57,56 +135,77 @@ impl std::fmt::Display for DfBinary {
 }
 
 impl DfBinary {
-  pub fn new(path: PathBuf) -> Self {
-    if !path.exists()
-      || !(path.file_name() == Some(OsStr::new("Dwarf Fortress.exe"))
-        || path.file_name() == Some(OsStr::new("dwarfort")))
-    {
-      return Self::default();
+  /// The DF executable at `path`, or in the macOS app bundle at `path`. Any name is accepted
+  /// for an executable with a DF version string in it.
+  pub fn new(path: PathBuf) -> Result<Self, DfBinaryError> {
+    let path = Self::bundle_executable(&path).unwrap_or(path);
+    if path.is_dir() {
+      return Err(DfBinaryError::NotDfExecutable);
     }
-
-    let os = Self::os(&path);
-    let checksum = Self::checksum(&path, os);
-    if checksum.is_err() {
-      return Self::default();
+    let identity = Identity::of_file(&path)?;
+    let known_name = path
+      .file_name()
+      .is_some_and(|name| EXECUTABLES.iter().any(|executable| name == *executable));
+    if identity.version.is_none() && !known_name {
+      return Err(DfBinaryError::NotDfExecutable);
     }
-    let parent = path.parent().expect("Unable to get parent dir");
+    let parent = path.parent().ok_or(DfBinaryError::NotDfExecutable)?;
+    let os = identity.os;
 
-    let data = std::fs::read(&path).expect("Unable to read file");
-    let version = Self::detect_df_version(&data);
-    let steam_api = Self::detect_steam_api(&data);
-
-    Self {
+    Ok(Self {
       path: path.clone(),
-      dir: parent.to_path_buf(),
-      checksum: checksum.expect("err check above"),
+      dir: Self::game_dir(parent),
+      checksum: identity.checksum,
       os,
-      version: version.unwrap_or_default(),
-      steam: steam_api,
+      version: identity.version.unwrap_or_default(),
+      build_id: identity.build_id,
+      steam: identity.steam,
+      compat: Self::compat(&path, os),
       valid: true,
       lib_path: Self::get_lib_path(parent, os, "dfhooks_dfint"),
       dfhooks_path: Self::get_lib_path(parent, os, "dfhooks"),
+    })
+  }
+
+  /// Executable of a macOS app bundle, named by `CFBundleExecutable` in its `Info.plist`.
+  pub fn bundle_executable(bundle: &Path) -> Option<PathBuf> {
+    if bundle.extension()? != "app" || !bundle.is_dir() {
+      return None;
     }
+    let contents = bundle.join("Contents");
+    let name = std::fs::read_to_string(contents.join("Info.plist"))
+      .ok()
+      .and_then(|plist| Some(BUNDLE_EXECUTABLE.captures(&plist)?[1].trim().to_string()))
+      .or_else(|| Some(bundle.file_stem()?.to_string_lossy().to_string()))?;
+    Some(contents.join("MacOS").join(name))
   }
 
-  fn os(path: &Path) -> OS {
-    if path.file_name() == Some(OsStr::new("Dwarf Fortress.exe")) {
-      OS::Windows
-    } else {
-      OS::Linux
+  /// App bundles keep the game files in `Contents/Resources`, apart from the executable.
+  fn game_dir(executable_dir: &Path) -> PathBuf {
+    let resources = executable_dir.with_file_name("Resources");
+    match executable_dir.ends_with("Contents/MacOS") && resources.join("data").is_dir() {
+      true => resources,
+      false => executable_dir.to_path_buf(),
     }
   }
 
-  fn checksum(path: &Path, os: OS) -> Result<u32> {
+  fn compat(path: &Path, os: OS) -> Option<Compat> {
     match os {
-      OS::Windows => {
-        let pefile = VecPE::from_disk_file(path)?;
-        Ok(pefile.get_nt_headers_64()?.file_header.time_date_stamp)
-      }
-      OS::Linux => {
-        let content = std::fs::read(path)?;
-        Ok(crc32fast::hash(&content))
-      }
+      OS::Windows if cfg!(not(windows)) => Self::compat_layer(path),
+      _ => None,
+    }
+  }
+
+  /// Steam runs the Windows builds in its libraries with Proton, which keeps its prefixes in
+  /// `steamapps/compatdata/<app>/pfx`. Any other `drive_c` is taken for a Wine prefix.
+  fn compat_layer(path: &Path) -> Option<Compat> {
+    let parts: Vec<_> = path.components().map(|part| part.as_os_str()).collect();
+    if parts.contains(&std::ffi::OsStr::new("steamapps")) {
+      Some(Compat::Proton)
+    } else if parts.contains(&std::ffi::OsStr::new("drive_c")) {
+      Some(Compat::Wine)
+    } else {
+      None
     }
   }
 
@@ -114,49 +213,166 @@ impl DfBinary {
     match os {
       OS::Windows => dir.join(format!("{name}.dll")),
       OS::Linux => dir.join(format!("lib{name}.so")),
+      OS::MacOS => dir.join(format!("lib{name}.dylib")),
     }
   }
 
   fn version_comparing_key(caps: &Captures) -> (u32, u32, u32) {
-    let parse_group = |idx: usize| -> u32 {
-      let m = caps.get(idx).unwrap().as_bytes();
-      std::str::from_utf8(m).unwrap().parse().unwrap()
+    // any file may be searched now, so digits that don't fit count as 0
+    let parse_group = |idx: usize| -> Option<u32> {
+      let m = caps.get(idx)?.as_bytes();
+      std::str::from_utf8(m).ok()?.parse().ok()
     };
 
-    let major = parse_group(2);
-    let minor = parse_group(3);
+    let major = parse_group(2).unwrap_or(0);
+    let minor = parse_group(3).unwrap_or(0);
 
-    let beta = caps
-      .get(6)
-      .map(|m| std::str::from_utf8(m.as_bytes()).unwrap().parse().unwrap())
-      .unwrap_or(MAX_BETA);
+    let beta = match caps.get(6) {
+      Some(_) => parse_group(6).unwrap_or(0),
+      None => MAX_BETA,
+    };
 
     (major, minor, beta)
   }
 
+  /// Key to order version strings like `51.01` and `50.11-beta3`, unknown versions are oldest.
+  pub fn version_key(version: &str) -> (u32, u32, u32) {
+    let (number, beta) = match version.split_once('-') {
+      Some((number, suffix)) => (
+        number,
+        suffix
+          .trim_start_matches(|char: char| !char.is_ascii_digit())
+          .parse()
+          .unwrap_or(0),
+      ),
+      None => (version, MAX_BETA),
+    };
+    match number.split_once('.') {
+      Some((major, minor)) => match (major.parse(), minor.parse()) {
+        (Ok(major), Ok(minor)) => (major, minor, beta),
+        _ => (0, 0, 0),
+      },
+      None => (0, 0, 0),
+    }
+  }
+
   pub fn detect_df_version(data: &[u8]) -> Option<String> {
-    let pattern = Regex::new(r"\x00((\d+)\.(\d+)(-([^\d]+)(\d*))?)\x00").unwrap();
-    let best_match = pattern
-      .captures_iter(data)
-      .max_by_key(|caps| DfBinary::version_comparing_key(caps))?;
+    // strings are packed, the closing null of one version can open the next one
+    let mut start = 0;
+    let matches = std::iter::from_fn(|| {
+      let caps = VERSION_PATTERN.captures_at(data, start)?;
+      start = caps.get(0)?.end() - 1;
+      Some(caps)
+    });
+    let best_match = matches.max_by_key(|caps| DfBinary::version_comparing_key(caps))?;
 
     let version_bytes = best_match.get(1)?.as_bytes();
     String::from_utf8(version_bytes.to_vec()).ok()
   }
-
-  pub fn detect_steam_api(data: &[u8]) -> bool {
-    let pattern = Regex::new(r"SteamAPI_Init").unwrap();
-    pattern.is_match(data)
-  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/binaries");
+
   #[test]
   fn bin() {
-    let bin = DfBinary::new(PathBuf::from("D:\\Downloads\\Dwarf Fortress.exe"));
-    println!("steam: {:?}, version: {:?}", bin.steam, bin.version);
+    let bin = DfBinary::new(Path::new(FIXTURES).join("Dwarf Fortress.exe")).unwrap();
+    assert!(bin.valid && bin.steam);
+    assert_eq!(bin.version, "51.01");
+    assert_eq!(bin.lib_path, Path::new(FIXTURES).join("dfhooks_dfint.dll"));
+  }
+
+  #[test]
+  fn formats_and_names() {
+    let dir = tempfile::tempdir().unwrap();
+    // renamed, told by its format
+    std::fs::copy(
+      Path::new(FIXTURES).join("Dwarf Fortress.exe"),
+      dir.path().join("df.exe"),
+    )
+    .unwrap();
+    let bin = DfBinary::new(dir.path().join("df.exe")).unwrap();
+    assert_eq!(bin.os, OS::Windows);
+    std::fs::copy(
+      Path::new(FIXTURES).join("dwarfort"),
+      dir.path().join("Dwarf Fortress.exe"),
+    )
+    .unwrap();
+    let bin = DfBinary::new(dir.path().join("Dwarf Fortress.exe")).unwrap();
+    assert_eq!(bin.os, OS::Linux);
+    assert_eq!(bin.lib_path, dir.path().join("libdfhooks_dfint.so"));
+
+    let bundle = Path::new(FIXTURES).join("Dwarf Fortress.app");
+    let bin = DfBinary::new(bundle.clone()).unwrap();
+    assert_eq!(bin.os, OS::MacOS);
+    assert_eq!(bin.path, bundle.join("Contents/MacOS/dwarfort"));
+    assert_eq!(bin.dir, bundle.join("Contents/Resources"));
+    assert_eq!(bin.lib_path, bundle.join("Contents/MacOS/libdfhooks_dfint.dylib"));
+    assert_eq!(bin.dfhooks_path, bundle.join("Contents/MacOS/libdfhooks.dylib"));
+    assert_eq!(bin.compat, None);
+  }
+
+  #[test]
+  fn errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let error = |path: PathBuf| DfBinary::new(path).err().unwrap();
+    assert!(matches!(error(dir.path().join("dwarfort")), DfBinaryError::NotFound));
+    assert!(matches!(
+      error(dir.path().to_path_buf()),
+      DfBinaryError::NotDfExecutable
+    ));
+
+    std::fs::write(dir.path().join("dwarfort"), "#!/bin/sh").unwrap();
+    assert!(matches!(
+      error(dir.path().join("dwarfort")),
+      DfBinaryError::NotDfExecutable
+    ));
+    std::fs::write(dir.path().join("dwarfort"), b"\x7fELF\x02\x01").unwrap();
+    assert!(matches!(
+      error(dir.path().join("dwarfort")),
+      DfBinaryError::CorruptHeader(_)
+    ));
+
+    // a PE32 optional header
+    let mut pe32 = std::fs::read(Path::new(FIXTURES).join("Dwarf Fortress.exe")).unwrap();
+    pe32[0x58] = 0x0b;
+    pe32[0x59] = 0x01;
+    std::fs::write(dir.path().join("Dwarf Fortress.exe"), pe32).unwrap();
+    assert!(matches!(
+      error(dir.path().join("Dwarf Fortress.exe")),
+      DfBinaryError::UnsupportedPe32
+    ));
+  }
+
+  #[test]
+  fn compat_layers() {
+    let steam = Path::new("/home/user/.local/share/Steam/steamapps/common/Dwarf Fortress/Dwarf Fortress.exe");
+    let prefix = Path::new("/home/user/.local/share/Steam/steamapps/compatdata/2801/pfx/drive_c/DF/Dwarf Fortress.exe");
+    let wine = Path::new("/home/user/.wine/drive_c/Games/DF/Dwarf Fortress.exe");
+    assert_eq!(DfBinary::compat_layer(steam), Some(Compat::Proton));
+    assert_eq!(DfBinary::compat_layer(prefix), Some(Compat::Proton));
+    assert_eq!(DfBinary::compat_layer(wine), Some(Compat::Wine));
+    assert_eq!(DfBinary::compat_layer(Path::new("/games/DF/Dwarf Fortress.exe")), None);
+    assert_eq!(DfBinary::compat(Path::new("/games/dwarfort"), OS::Linux), None);
+    assert_eq!(
+      DfBinary::compat(wine, OS::Windows),
+      cfg!(not(windows)).then_some(Compat::Wine)
+    );
+  }
+
+  #[test]
+  fn version_order() {
+    assert!(DfBinary::version_key("51.01") > DfBinary::version_key("50.14"));
+    assert!(DfBinary::version_key("51.01") > DfBinary::version_key("51.01-beta9"));
+    assert!(DfBinary::version_key("51.01-beta10") > DfBinary::version_key("51.01-beta9"));
+    assert_eq!(DfBinary::version_key("Unknown"), (0, 0, 0));
+    assert_eq!(DfBinary::version_key(""), (0, 0, 0));
+
+    // packed strings, an empty beta number and numbers too long for u32
+    let data = b"\x0050.14\x0051.01\x0051.02-beta\x0099999999999.1\x00";
+    assert_eq!(DfBinary::detect_df_version(data).as_deref(), Some("51.02-beta"));
   }
 }
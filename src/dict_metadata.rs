@@ -1,6 +1,10 @@
 use anyhow::Result;
+use std::collections::BTreeMap;
 
-use crate::fetch;
+use crate::{
+  fetch,
+  mirrors::{Mirrors, mirrors},
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Manifest {
@@ -10,6 +14,9 @@ pub struct Manifest {
   pub font: String,
   pub encoding: String,
   pub code: Option<String>,
+  /// Optional SHA-256 of the files, keyed by field name (`csv`, `font`, `encoding`)
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub sha256: BTreeMap<String, String>,
 }
 
 impl Default for Manifest {
@@ -21,19 +28,37 @@ impl Default for Manifest {
       font: "".to_string(),
       encoding: "".to_string(),
       code: None,
+      sha256: BTreeMap::new(),
     }
   }
 }
 
-#[derive(Default)]
+impl Manifest {
+  /// URLs of the files by field name, in the order of `Installer::dict_files`.
+  pub fn files(&self) -> [(&'static str, String); 3] {
+    [
+      ("csv", self.csv.clone()),
+      ("font", self.font.clone()),
+      ("encoding", self.encoding.clone()),
+    ]
+  }
+}
+
+#[derive(Default, Clone)]
 pub struct DictMetadata {
   pub manifest: Manifest,
   pub vec_manifests: Vec<Manifest>,
+  /// Date of the local copy the manifests come from, `None` if they were just fetched
+  pub cached: Option<String>,
 }
 
 impl DictMetadata {
   pub async fn from_url(url: &str, pick_language: Option<String>) -> Result<Self> {
-    let manifests: Vec<Manifest> = fetch::fetch_json(url)?;
+    Self::from_mirrors(&mirrors(), url, pick_language).await
+  }
+
+  pub async fn from_mirrors(mirrors: &Mirrors, url: &str, pick_language: Option<String>) -> Result<Self> {
+    let (manifests, cached): (Vec<Manifest>, _) = fetch::fetch_signed_json_from(mirrors, url)?;
 
     let picked = match pick_language {
       Some(language) => {
@@ -49,6 +74,7 @@ impl DictMetadata {
     Ok(Self {
       manifest: picked,
       vec_manifests: manifests,
+      cached,
     })
   }
 
@@ -0,0 +1,637 @@
+//! Finding DF installations on this machine.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::df_binary::DfBinary;
+
+/// Steam app id of Dwarf Fortress.
+pub const DF_APP_ID: &str = "975370";
+/// Executable names of the Windows and the Linux build.
+pub const EXECUTABLES: [&str; 2] = ["Dwarf Fortress.exe", "dwarfort"];
+/// Directory levels searched below a scan root by default.
+pub const DEFAULT_SCAN_DEPTH: usize = 3;
+
+/// Directory searched for DF executables, down to `depth` levels below it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanRoot {
+  pub path: PathBuf,
+  #[serde(default = "default_depth")]
+  pub depth: usize,
+}
+
+fn default_depth() -> usize {
+  DEFAULT_SCAN_DEPTH
+}
+
+impl ScanRoot {
+  pub fn new(path: impl Into<PathBuf>, depth: usize) -> Self {
+    Self {
+      path: path.into(),
+      depth,
+    }
+  }
+}
+
+/// Valid DF installations from Steam, Proton and Wine prefixes, itch.io, the common
+/// install folders and `roots`, the newest version first.
+pub fn discover(roots: &[ScanRoot]) -> Vec<DfBinary> {
+  let mut candidates = steam_installs();
+  candidates.extend(prefix_installs());
+  candidates.extend(itch_installs());
+  for root in common_roots().iter().chain(roots) {
+    candidates.extend(find_executables(&expand_home(&root.path.to_string_lossy()), root.depth));
+  }
+  rank(candidates)
+}
+
+/// Valid binaries among `candidates` without duplicates, the newest version first.
+pub fn rank(candidates: Vec<PathBuf>) -> Vec<DfBinary> {
+  let mut seen: Vec<PathBuf> = vec![];
+  let mut found: Vec<DfBinary> = candidates
+    .into_iter()
+    .filter(|path| {
+      let canonical = path.canonicalize().unwrap_or(path.clone());
+      let new = !seen.contains(&canonical);
+      seen.push(canonical);
+      new
+    })
+    .filter_map(|path| DfBinary::new(path).ok())
+    .collect();
+  // stable, so Steam installs stay first among equal versions
+  found.sort_by_key(|bin| std::cmp::Reverse(DfBinary::version_key(&bin.version)));
+  found
+}
+
+/// Folders classic and portable builds are usually unpacked to.
+fn common_roots() -> Vec<ScanRoot> {
+  let mut roots: Vec<ScanRoot> = ["~/Games", "~/games", "~/Desktop", "~/Downloads", "~/Applications"]
+    .iter()
+    .map(|path| ScanRoot::new(expand_home(path), DEFAULT_SCAN_DEPTH))
+    .collect();
+  roots.extend(
+    [
+      "/opt",
+      "C:\\Games",
+      "D:\\Games",
+      "C:\\Program Files",
+      "C:\\Program Files (x86)",
+    ]
+    .map(|path| ScanRoot::new(path, 1)),
+  );
+  roots
+}
+
+/// DF executables in `root` and up to `depth` levels below it, including the ones of macOS
+/// app bundles named like DF. Hidden folders and links to folders are skipped.
+pub fn find_executables(root: &Path, depth: usize) -> Vec<PathBuf> {
+  let mut found = vec![];
+  let mut pending = vec![(root.to_path_buf(), 0)];
+  while let Some((dir, level)) = pending.pop() {
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+      continue;
+    };
+    for entry in entries.flatten() {
+      let Ok(file_type) = entry.file_type() else {
+        continue;
+      };
+      let name = entry.file_name();
+      let bundle = file_type
+        .is_dir()
+        .then(|| name.to_string_lossy().to_lowercase())
+        .filter(|name| name.ends_with(".app"));
+      if let Some(bundle) = bundle {
+        if bundle.contains("dwarf")
+          && let Some(executable) = DfBinary::bundle_executable(&entry.path())
+          && executable.is_file()
+        {
+          found.push(executable);
+        }
+      } else if file_type.is_dir() {
+        if level < depth && !name.to_string_lossy().starts_with('.') {
+          pending.push((entry.path(), level + 1));
+        }
+      } else if EXECUTABLES.iter().any(|executable| name == *executable) {
+        found.push(entry.path());
+      }
+    }
+  }
+  found.sort();
+  found
+}
+
+/// Every DF executable installed by the itch.io app.
+pub fn itch_installs() -> Vec<PathBuf> {
+  let Some(config) = dirs::config_dir() else {
+    return vec![];
+  };
+  itch_installs_in(&config.join("itch"))
+}
+
+/// DF executables in the install locations of the itch.io app with its data in `itch_dir`.
+///
+/// Every game the app installs gets a folder with a `.itch/receipt.json.gz` naming the game.
+pub fn itch_installs_in(itch_dir: &Path) -> Vec<PathBuf> {
+  let mut found = vec![];
+  for location in itch_locations(itch_dir) {
+    let Ok(entries) = std::fs::read_dir(&location) else {
+      continue;
+    };
+    for entry in entries.flatten() {
+      let dir = entry.path();
+      if itch_game_title(&dir).is_some_and(|title| title.to_lowercase().contains("dwarf fortress")) {
+        found.extend(find_executables(&dir, 2));
+      }
+    }
+  }
+  found
+}
+
+/// The default `apps` folder and the install locations added in the app's preferences.
+fn itch_locations(itch_dir: &Path) -> Vec<PathBuf> {
+  let mut locations = vec![itch_dir.join("apps")];
+  let preferences: Option<serde_json::Value> = std::fs::read_to_string(itch_dir.join("preferences.json"))
+    .ok()
+    .and_then(|text| serde_json::from_str(&text).ok());
+  if let Some(serde_json::Value::Object(extra)) = preferences
+    .as_ref()
+    .and_then(|value| value.get("installLocations"))
+  {
+    for location in extra.values() {
+      if let Some(path) = location.get("path").and_then(|path| path.as_str()) {
+        locations.push(PathBuf::from(path));
+      }
+    }
+  }
+  locations
+}
+
+/// Title of the game installed in `dir`, from its gzipped itch receipt.
+fn itch_game_title(dir: &Path) -> Option<String> {
+  let file = std::fs::File::open(dir.join(".itch").join("receipt.json.gz")).ok()?;
+  let mut text = String::new();
+  flate2::read::GzDecoder::new(file)
+    .read_to_string(&mut text)
+    .ok()?;
+  let receipt: serde_json::Value = serde_json::from_str(&text).ok()?;
+  Some(receipt.get("game")?.get("title")?.as_str()?.to_string())
+}
+
+/// Every DF executable in the known Steam libraries.
+pub fn steam_installs() -> Vec<PathBuf> {
+  steam_installs_in(&steam_roots())
+}
+
+/// `~` or `~/...` resolved against the home directory, other paths as they are.
+pub fn expand_home(path: &str) -> PathBuf {
+  match (path.strip_prefix('~'), dirs::home_dir()) {
+    (Some(""), Some(home)) => home,
+    (Some(rest), Some(home)) if rest.starts_with(['/', '\\']) => home.join(&rest[1..]),
+    _ => PathBuf::from(path),
+  }
+}
+
+/// Default Steam directories of the native, Flatpak and Snap clients.
+fn steam_roots() -> Vec<PathBuf> {
+  let mut roots: Vec<PathBuf> = [
+    "~/.local/share/Steam",
+    "~/.steam/steam",
+    "~/.steam/root",
+    "~/.var/app/com.valvesoftware.Steam/.local/share/Steam",
+    "~/.var/app/com.valvesoftware.Steam/data/Steam",
+    "~/snap/steam/common/.local/share/Steam",
+    "~/Library/Application Support/Steam",
+  ]
+  .iter()
+  .map(|path| expand_home(path))
+  .collect();
+  roots.extend(["C:\\Program Files (x86)\\Steam", "C:\\Program Files\\Steam"].map(PathBuf::from));
+  roots
+}
+
+/// DF executables in the libraries of the Steam installations at `roots`.
+pub fn steam_installs_in(roots: &[PathBuf]) -> Vec<PathBuf> {
+  let mut found = vec![];
+  for library in steam_libraries(roots) {
+    let Some(dir) = app_dir(&library) else {
+      continue;
+    };
+    for name in EXECUTABLES {
+      let path = dir.join(name);
+      if path.is_file() && !found.contains(&path) {
+        found.push(path);
+      }
+    }
+  }
+  found
+}
+
+/// Libraries of the Steam installations at `roots`, without duplicates.
+fn steam_libraries(roots: &[PathBuf]) -> Vec<PathBuf> {
+  let mut libraries: Vec<PathBuf> = vec![];
+  for root in roots.iter().filter(|root| root.is_dir()) {
+    for library in std::iter::once(root.clone()).chain(library_folders(root)) {
+      // ~/.steam/steam is usually a link to ~/.local/share/Steam
+      let library = library.canonicalize().unwrap_or(library);
+      if !libraries.contains(&library) {
+        libraries.push(library);
+      }
+    }
+  }
+  libraries
+}
+
+/// Windows builds in the Proton prefixes of the known Steam libraries and the default Wine
+/// prefix, e.g. a classic build added to Steam as a non-Steam game.
+pub fn prefix_installs() -> Vec<PathBuf> {
+  let mut found = proton_installs_in(&steam_roots());
+  found.extend(drive_c_installs(&expand_home("~/.wine/drive_c")));
+  found
+}
+
+/// Windows builds in the `steamapps/compatdata/<app id>/pfx` prefixes of the libraries of
+/// the Steam installations at `roots`.
+pub fn proton_installs_in(roots: &[PathBuf]) -> Vec<PathBuf> {
+  let mut found = vec![];
+  for library in steam_libraries(roots) {
+    let Ok(prefixes) = std::fs::read_dir(library.join("steamapps").join("compatdata")) else {
+      continue;
+    };
+    for prefix in prefixes.flatten() {
+      found.extend(drive_c_installs(&prefix.path().join("pfx").join("drive_c")));
+    }
+  }
+  found
+}
+
+/// Windows builds on the `C:` drive of a prefix, the system folder is skipped.
+fn drive_c_installs(drive_c: &Path) -> Vec<PathBuf> {
+  let Ok(entries) = std::fs::read_dir(drive_c) else {
+    return vec![];
+  };
+  let mut found = vec![];
+  for entry in entries.flatten() {
+    if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) && !entry.file_name().eq_ignore_ascii_case("windows")
+    {
+      found.extend(
+        find_executables(&entry.path(), DEFAULT_SCAN_DEPTH)
+          .into_iter()
+          .filter(|path| path.ends_with(EXECUTABLES[0])),
+      );
+    }
+  }
+  found
+}
+
+/// Library folders listed in `steamapps/libraryfolders.vdf` of a Steam installation.
+fn library_folders(root: &Path) -> Vec<PathBuf> {
+  let Some(vdf) = read_vdf(&root.join("steamapps").join("libraryfolders.vdf")) else {
+    return vec![];
+  };
+  let Some(Vdf::Object(entries)) = vdf.get("libraryfolders") else {
+    return vec![];
+  };
+  entries
+    .iter()
+    .filter(|(key, _)| key.parse::<u32>().is_ok())
+    .filter_map(|(_, entry)| match entry {
+      // older clients list the paths directly
+      Vdf::Value(path) => Some(PathBuf::from(path)),
+      Vdf::Object(_) => match entry.get("apps") {
+        // skip libraries known not to have DF
+        Some(apps) if apps.get(DF_APP_ID).is_none() => None,
+        _ => entry.get("path")?.value().map(PathBuf::from),
+      },
+    })
+    .collect()
+}
+
+/// Install directory of DF in a library, from `steamapps/appmanifest_975370.acf`.
+fn app_dir(library: &Path) -> Option<PathBuf> {
+  let steamapps = library.join("steamapps");
+  let manifest = read_vdf(&steamapps.join(format!("appmanifest_{DF_APP_ID}.acf")))?;
+  let install_dir = manifest.get("AppState")?.get("installdir")?.value()?;
+  Some(steamapps.join("common").join(install_dir))
+}
+
+fn read_vdf(path: &Path) -> Option<Vdf> {
+  Vdf::parse(&std::fs::read_to_string(path).ok()?)
+}
+
+/// Node of Valve's text KeyValues format used by the Steam client files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Vdf {
+  Value(String),
+  Object(Vec<(String, Vdf)>),
+}
+
+impl Vdf {
+  /// Parse a document, the top level is an object of its root keys.
+  fn parse(text: &str) -> Option<Self> {
+    Self::parse_object(&mut tokenize(text)?.into_iter(), false)
+  }
+
+  fn parse_object(tokens: &mut impl Iterator<Item = Token>, nested: bool) -> Option<Self> {
+    let mut entries = vec![];
+    loop {
+      let key = match tokens.next() {
+        Some(Token::String(key)) => key,
+        Some(Token::Close) if nested => return Some(Vdf::Object(entries)),
+        None if !nested => return Some(Vdf::Object(entries)),
+        _ => return None,
+      };
+      let value = match tokens.next()? {
+        Token::String(value) => Vdf::Value(value),
+        Token::Open => Self::parse_object(tokens, true)?,
+        Token::Close => return None,
+      };
+      entries.push((key, value));
+    }
+  }
+
+  /// Child by key, keys are case insensitive.
+  fn get(&self, key: &str) -> Option<&Vdf> {
+    match self {
+      Vdf::Object(entries) => entries
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value),
+      Vdf::Value(_) => None,
+    }
+  }
+
+  fn value(&self) -> Option<&str> {
+    match self {
+      Vdf::Value(value) => Some(value),
+      Vdf::Object(_) => None,
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+  String(String),
+  Open,
+  Close,
+}
+
+fn tokenize(text: &str) -> Option<Vec<Token>> {
+  let mut tokens = vec![];
+  let mut chars = text.chars().peekable();
+  while let Some(char) = chars.next() {
+    match char {
+      '{' => tokens.push(Token::Open),
+      '}' => tokens.push(Token::Close),
+      '"' => {
+        let mut value = String::new();
+        loop {
+          match chars.next()? {
+            '"' => break,
+            '\\' => match chars.next()? {
+              'n' => value.push('\n'),
+              't' => value.push('\t'),
+              other => value.push(other),
+            },
+            other => value.push(other),
+          }
+        }
+        tokens.push(Token::String(value));
+      }
+      '/' if chars.peek() == Some(&'/') => {
+        for char in chars.by_ref() {
+          if char == '\n' {
+            break;
+          }
+        }
+      }
+      char if char.is_whitespace() => {}
+      // unquoted token, conditionals like [$WIN32] aren't used in the files read here
+      _ => {
+        let mut value = String::from(char);
+        while let Some(&next) = chars.peek() {
+          if next.is_whitespace() || matches!(next, '{' | '}' | '"') {
+            break;
+          }
+          value.push(next);
+          chars.next();
+        }
+        tokens.push(Token::String(value));
+      }
+    }
+  }
+  Some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+  /// Copy a fixture to `dest`, with `{name}` placeholders replaced by paths.
+  fn fixture(name: &str, dest: &Path, replace: &[(&str, &Path)]) {
+    let mut text = std::fs::read_to_string(Path::new(FIXTURES).join(name)).unwrap();
+    for (placeholder, path) in replace {
+      // paths are escaped like in the files written by Steam
+      let escaped = path.display().to_string().replace('\\', "\\\\");
+      text = text.replace(&format!("{{{placeholder}}}"), &escaped);
+    }
+    std::fs::create_dir_all(dest.parent().unwrap()).unwrap();
+    std::fs::write(dest, text).unwrap();
+  }
+
+  #[test]
+  fn parse_vdf() {
+    let vdf = Vdf::parse(
+      r#"
+      // comment
+      "AppState"
+      {
+        "appid"   "975370"
+        "InstallDir" "Dwarf \"Fortress\""
+        "path" "D:\\Games"
+        "UserConfig" { "language" english }
+      }"#,
+    )
+    .unwrap();
+    let app = vdf.get("appstate").unwrap();
+    assert_eq!(app.get("installdir").unwrap().value(), Some("Dwarf \"Fortress\""));
+    assert_eq!(app.get("path").unwrap().value(), Some("D:\\Games"));
+    assert_eq!(
+      app
+        .get("userconfig")
+        .unwrap()
+        .get("language")
+        .unwrap()
+        .value(),
+      Some("english")
+    );
+    assert_eq!(Vdf::parse(r#""a" { "b" "c""#), None);
+    assert_eq!(Vdf::parse(r#""a" "b" }"#), None);
+  }
+
+  #[test]
+  fn finds_installs_in_all_libraries() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("Steam");
+    let library = dir.path().join("SteamLibrary");
+    let other = dir.path().join("OtherLibrary");
+    fixture(
+      "steam/libraryfolders.vdf",
+      &root.join("steamapps/libraryfolders.vdf"),
+      &[("root", &root), ("library", &library), ("other", &other)],
+    );
+    fixture(
+      "steam/appmanifest_975370.acf",
+      &library.join("steamapps/appmanifest_975370.acf"),
+      &[],
+    );
+    // listed without DF, the manifest is ignored
+    fixture(
+      "steam/appmanifest_975370.acf",
+      &other.join("steamapps/appmanifest_975370.acf"),
+      &[],
+    );
+    let df_dir = library.join("steamapps/common/Dwarf Fortress");
+    std::fs::create_dir_all(&df_dir).unwrap();
+    std::fs::write(df_dir.join("dwarfort"), "").unwrap();
+    std::fs::create_dir_all(other.join("steamapps/common/Dwarf Fortress")).unwrap();
+    std::fs::write(other.join("steamapps/common/Dwarf Fortress/dwarfort"), "").unwrap();
+
+    let found = steam_installs_in(&[root.clone(), root, dir.path().join("missing")]);
+    assert_eq!(
+      found,
+      vec![
+        df_dir
+          .canonicalize()
+          .unwrap_or(df_dir.clone())
+          .join("dwarfort")
+      ]
+    );
+  }
+
+  #[test]
+  fn old_library_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("Steam");
+    let library = dir.path().join("SteamLibrary");
+    fixture(
+      "steam/libraryfolders_old.vdf",
+      &root.join("steamapps/libraryfolders.vdf"),
+      &[("library", &library)],
+    );
+    assert_eq!(library_folders(&root), vec![library]);
+  }
+
+  /// Copy of the Linux build fixture that reports `version`.
+  fn binary(dir: &Path, version: &str) -> PathBuf {
+    const VERSIONS: &[u8] = b"\x0050.14\x0051.01-beta2\x0051.01\x00";
+    let mut data = std::fs::read(Path::new(FIXTURES).join("binaries/dwarfort")).unwrap();
+    let start = data
+      .windows(VERSIONS.len())
+      .position(|window| window == VERSIONS)
+      .unwrap();
+    let mut replacement = format!("\0{version}\0").into_bytes();
+    replacement.resize(VERSIONS.len(), 0);
+    data[start..start + VERSIONS.len()].copy_from_slice(&replacement);
+
+    std::fs::create_dir_all(dir).unwrap();
+    let path = dir.join("dwarfort");
+    std::fs::write(&path, data).unwrap();
+    path
+  }
+
+  #[test]
+  fn scan_depth() {
+    let dir = tempfile::tempdir().unwrap();
+    let shallow = binary(&dir.path().join("df_51_01"), "51.01");
+    let deep = binary(&dir.path().join("games/classic/df_50_14"), "50.14");
+    binary(&dir.path().join(".hidden"), "51.02");
+    let bundle = dir.path().join("Dwarf Fortress.app/Contents");
+    std::fs::create_dir_all(bundle.join("MacOS")).unwrap();
+    std::fs::write(bundle.join("MacOS/Dwarf Fortress"), "").unwrap();
+    let app = bundle.join("MacOS/Dwarf Fortress");
+
+    assert_eq!(find_executables(dir.path(), 1), vec![app.clone(), shallow.clone()]);
+    assert_eq!(find_executables(dir.path(), 3), vec![app, shallow, deep]);
+    assert!(find_executables(&dir.path().join("missing"), 3).is_empty());
+  }
+
+  #[test]
+  fn ranked_by_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = binary(&dir.path().join("old"), "50.14");
+    let beta = binary(&dir.path().join("beta"), "51.01-beta3");
+    let new = binary(&dir.path().join("new"), "51.01");
+    let invalid = dir.path().join("other/dwarfort");
+
+    let ranked: Vec<PathBuf> = rank(vec![old.clone(), beta.clone(), invalid, new.clone(), old.clone()])
+      .into_iter()
+      .map(|bin| bin.path)
+      .collect();
+    assert_eq!(ranked, vec![new, beta, old]);
+  }
+
+  #[test]
+  fn itch_receipts() {
+    let dir = tempfile::tempdir().unwrap();
+    let itch = dir.path().join("itch");
+    let location = dir.path().join("Games/itch");
+    std::fs::create_dir_all(&itch).unwrap();
+    std::fs::write(
+      itch.join("preferences.json"),
+      serde_json::json!({ "installLocations": { "extra": { "path": location } } }).to_string(),
+    )
+    .unwrap();
+    let receipt = std::fs::read(Path::new(FIXTURES).join("itch/receipt.json")).unwrap();
+    let write_receipt = |game: &Path, receipt: &[u8]| {
+      std::fs::create_dir_all(game.join(".itch")).unwrap();
+      let file = std::fs::File::create(game.join(".itch/receipt.json.gz")).unwrap();
+      let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+      std::io::Write::write_all(&mut encoder, receipt).unwrap();
+      encoder.finish().unwrap();
+    };
+
+    let default = itch.join("apps/dwarf-fortress");
+    write_receipt(&default, &receipt);
+    let default = binary(&default.join("df_51_01"), "51.01");
+    let extra = location.join("dwarf-fortress");
+    write_receipt(&extra, &receipt);
+    let extra = binary(&extra, "50.14");
+    // another game with a file of the same name
+    let other = itch.join("apps/other");
+    write_receipt(&other, br#"{"game":{"title":"Other"}}"#);
+    binary(&other, "1.0");
+
+    assert_eq!(itch_installs_in(&itch), vec![default, extra]);
+  }
+
+  #[test]
+  fn proton_prefixes() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("Steam");
+    let drive_c = root.join("steamapps/compatdata/2801493127/pfx/drive_c");
+    let df_dir = drive_c.join("users/steamuser/DF");
+    std::fs::create_dir_all(&df_dir).unwrap();
+    std::fs::write(df_dir.join("Dwarf Fortress.exe"), "").unwrap();
+    std::fs::create_dir_all(drive_c.join("windows/DF")).unwrap();
+    std::fs::write(drive_c.join("windows/DF/Dwarf Fortress.exe"), "").unwrap();
+    // Linux builds don't run in a prefix
+    std::fs::write(df_dir.join("dwarfort"), "").unwrap();
+
+    let root = root.canonicalize().unwrap();
+    assert_eq!(
+      proton_installs_in(std::slice::from_ref(&root)),
+      vec![root.join("steamapps/compatdata/2801493127/pfx/drive_c/users/steamuser/DF/Dwarf Fortress.exe")]
+    );
+  }
+
+  #[test]
+  fn home_expansion() {
+    let home = dirs::home_dir().unwrap();
+    assert_eq!(expand_home("~"), home);
+    assert_eq!(expand_home("~/.local/share/Steam"), home.join(".local/share/Steam"));
+    assert_eq!(expand_home("/opt/~user"), PathBuf::from("/opt/~user"));
+    assert_eq!(expand_home("~user/Steam"), PathBuf::from("~user/Steam"));
+  }
+}
@@ -1,84 +1,619 @@
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
+use sha2::{Digest, Sha256};
 use std::{
-  io::Read,
-  path::PathBuf,
-  sync::atomic::{AtomicUsize, Ordering},
+  fs::OpenOptions,
+  io::{Read, Write},
+  path::{Path, PathBuf},
+  time::{Duration, Instant},
 };
 
-use crate::{constants::BASE_URL, fetch};
+use crate::{
+  agent::agent,
+  cache::Cache,
+  constants::{MANIFEST_PUBLIC_KEY, SIGNATURE_EXTENSION},
+  http_cache::http_cache,
+  mirrors::{Mirrors, file_url_path, mirrors},
+  progress::{Progress, Reporter},
+  signature,
+  transaction::Transaction,
+  utils::{checksum_for_files, sha256_hex},
+};
 
-static BASE_URL_INDEX: AtomicUsize = AtomicUsize::new(0);
+/// Directory inside the download root where unfinished downloads are kept between attempts.
+pub const PARTIAL_DIR: &str = ".partial";
+const CHUNK_SIZE: usize = 64 * 1024;
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
 
-pub fn get_base_url() -> &'static str {
-  let index = BASE_URL_INDEX.load(Ordering::Relaxed);
-  BASE_URL[index.min(BASE_URL.len() - 1)]
+/// A file to download, `sha256` is checked before the file is written.
+#[derive(Debug, Clone)]
+pub struct Download {
+  pub url: String,
+  pub file: PathBuf,
+  pub sha256: Option<String>,
 }
 
-pub fn switch_to_next_base_url() {
-  BASE_URL_INDEX
-    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |index| {
-      let max_index = BASE_URL.len() - 1;
-      if index < max_index { Some(index + 1) } else { None }
-    })
-    .ok();
+pub fn fetch_json<T: for<'de> serde::Deserialize<'de>>(path: &str) -> Result<T> {
+  Ok(serde_json::from_slice(&fetch_bytes(path)?)?)
 }
 
-pub fn fetch_json<T: for<'de> serde::Deserialize<'de>>(path: &str) -> Result<T> {
-  let base_url = get_base_url();
-  let url = format!("{}{}", base_url, path);
-
-  match ureq::get(&url).call() {
-    Ok(res) => Ok(serde_json::from_reader(res.into_body().into_reader()).unwrap()),
-    Err(e) => {
-      if get_base_url() == base_url {
-        switch_to_next_base_url();
-        if get_base_url() != base_url {
-          return fetch_json(path);
-        }
-      } else {
-        return fetch_json(path);
-      }
+pub fn fetch_bytes(path: &str) -> Result<Vec<u8>> {
+  fetch_bytes_from(&mirrors(), path)
+}
 
-      Err(anyhow::Error::from(e))
+pub fn fetch_bytes_from(mirrors: &Mirrors, path: &str) -> Result<Vec<u8>> {
+  mirrors.run(path, |url| {
+    if let Some(path) = file_url_path(url) {
+      return Ok(std::fs::read(path)?);
     }
+    let mut bytes = Vec::new();
+    agent()
+      .get(url)
+      .call()?
+      .into_body()
+      .into_reader()
+      .read_to_end(&mut bytes)?;
+    Ok(bytes)
+  })
+}
+
+/// Fetch JSON together with its detached signature, refusing content that isn't signed
+/// with the embedded manifest key.
+pub fn fetch_signed_json<T: for<'de> serde::Deserialize<'de>>(path: &str) -> Result<(T, Option<String>)> {
+  fetch_signed_json_from(&mirrors(), path)
+}
+
+/// Manifests go through the HTTP cache, a cached copy is used when no mirror is reachable.
+/// Returns the date of the cached copy in that case.
+pub fn fetch_signed_json_from<T: for<'de> serde::Deserialize<'de>>(
+  mirrors: &Mirrors,
+  path: &str,
+) -> Result<(T, Option<String>)> {
+  let data = http_cache().fetch(mirrors, path)?;
+  let signature = http_cache()
+    .fetch(mirrors, &format!("{path}{SIGNATURE_EXTENSION}"))
+    .with_context(|| format!("Unable to fetch signature of {path}, refusing unsigned manifest"))?;
+  signature::verify(&data.body, &signature.body, &MANIFEST_PUBLIC_KEY).with_context(|| format!("Rejected {path}"))?;
+  Ok((serde_json::from_slice(&data.body)?, data.cached.or(signature.cached)))
+}
+
+pub fn verify_sha256(data: &[u8], url: &str, expected: Option<&str>) -> Result<()> {
+  verify_digest(&sha256_hex(data), url, expected)
+}
+
+/// Compare the hex SHA-256 of a file that was hashed while it was written.
+pub fn verify_digest(actual: &str, url: &str, expected: Option<&str>) -> Result<()> {
+  if let Some(expected) = expected
+    && !actual.eq_ignore_ascii_case(expected)
+  {
+    bail!("Integrity check failed for {url}: expected SHA-256 {expected}, got {actual}");
   }
+  Ok(())
 }
 
-pub fn fetch_bytes(path: &str) -> Result<Vec<u8>> {
-  let base_url = get_base_url();
-  let url = format!("{}{}", base_url, path);
-
-  match ureq::get(&url).call() {
-    Ok(res) => {
-      let mut bytes = Vec::new();
-      res.into_body().into_reader().read_to_end(&mut bytes)?;
-      Ok(bytes)
-    }
-    Err(e) => {
-      if get_base_url() == base_url {
-        switch_to_next_base_url();
-        if get_base_url() != base_url {
-          return fetch_bytes(path);
-        }
-      } else {
-        return fetch_bytes(path);
+/// Size of the whole file from a `Content-Range: bytes 100-199/200` header.
+fn content_range_total(value: &str) -> Option<u64> {
+  value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+/// First byte of the body from a `Content-Range: bytes 100-199/200` header.
+fn content_range_start(value: &str) -> Option<u64> {
+  value
+    .trim()
+    .strip_prefix("bytes ")?
+    .split_once('-')?
+    .0
+    .trim()
+    .parse()
+    .ok()
+}
+
+/// Append the rest of `url` to `partial`, resuming from its current size if the server supports ranges.
+/// Returns the hex SHA-256 of the whole file, hashed as it is written.
+fn stream_to_file(url: &str, partial: &Path, report: &impl Fn(u64, Option<u64>)) -> Result<String> {
+  // local mirrors are copied as a whole, there is nothing to resume
+  if let Some(path) = file_url_path(url) {
+    let mut hasher = Sha256::new();
+    let mut source = std::fs::File::open(path)?;
+    let mut file = std::fs::File::create(partial)?;
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut size = 0;
+    loop {
+      let read = source.read(&mut buffer)?;
+      if read == 0 {
+        break;
       }
+      hasher.update(&buffer[..read]);
+      file.write_all(&buffer[..read])?;
+      size += read as u64;
+    }
+    report(size, Some(size));
+    return Ok(format!("{:x}", hasher.finalize()));
+  }
+
+  let offset = partial
+    .metadata()
+    .map(|metadata| metadata.len())
+    .unwrap_or(0);
+  let mut request = agent().get(url);
+  if offset > 0 {
+    request = request.header("Range", format!("bytes={offset}-"));
+  }
+  let response = match request.call() {
+    Err(ureq::Error::StatusCode(416)) if offset > 0 => {
+      // the partial file doesn't fit the remote one, start over
+      std::fs::remove_file(partial)?;
+      return stream_to_file(url, partial, report);
+    }
+    response => response?,
+  };
+
+  let header = |name: &str| {
+    response
+      .headers()
+      .get(name)
+      .and_then(|value| value.to_str().ok())
+      .map(str::to_string)
+  };
+  let length: Option<u64> = header("content-length").and_then(|value| value.parse().ok());
+  let resumed = response.status() == 206;
+  if resumed && header("content-range").and_then(|value| content_range_start(&value)) != Some(offset) {
+    // the body doesn't continue the partial file, start over
+    drop(response);
+    std::fs::remove_file(partial)?;
+    return stream_to_file(url, partial, report);
+  }
+  let (mut downloaded, total) = if resumed {
+    let total = header("content-range")
+      .and_then(|value| content_range_total(&value))
+      .or(length.map(|length| length + offset));
+    (offset, total)
+  } else {
+    (0, length)
+  };
 
-      Err(anyhow::Error::from(e))
+  let mut hasher = Sha256::new();
+  let mut file = if resumed {
+    // the part downloaded before is hashed from the disk, in chunks
+    std::io::copy(&mut std::fs::File::open(partial)?, &mut hasher)?;
+    OpenOptions::new().append(true).open(partial)?
+  } else {
+    std::fs::File::create(partial)?
+  };
+  let mut reader = response.into_body().into_reader();
+  let mut buffer = vec![0; CHUNK_SIZE];
+  let mut reported = Instant::now();
+  report(downloaded, total);
+  loop {
+    // errors of the connection are `ureq::Error`, so they count as transient, unlike errors of the local file
+    let read = reader.read(&mut buffer).map_err(ureq::Error::Io)?;
+    if read == 0 {
+      break;
     }
+    hasher.update(&buffer[..read]);
+    file.write_all(&buffer[..read])?;
+    downloaded += read as u64;
+    if reported.elapsed() >= REPORT_INTERVAL {
+      report(downloaded, total);
+      reported = Instant::now();
+    }
+  }
+  report(downloaded, total);
+
+  if let Some(total) = total
+    && downloaded < total
+  {
+    let message = format!("Connection closed after {downloaded} of {total} bytes of {url}");
+    return Err(ureq::Error::Io(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, message)).into());
   }
+  Ok(format!("{:x}", hasher.finalize()))
 }
 
-pub async fn download_to_file(url: &str, file: &PathBuf) -> Result<()> {
-  let data: Vec<u8> = fetch::fetch_bytes(url)?;
-  std::fs::write(file, &data)?;
+/// Stream `item` from `mirrors` to `staged` in chunks. Unfinished downloads stay in `partial_dir` and
+/// are resumed with a range request on the next attempt, the finished file is checked against its SHA-256.
+pub async fn download_to_file(
+  mirrors: &Mirrors,
+  cache: Option<&Cache>,
+  item: &Download,
+  staged: &Path,
+  partial_dir: &Path,
+  progress: &Reporter,
+) -> Result<()> {
+  if let (Some(cache), Some(sha256)) = (cache, &item.sha256)
+    && cache.get(sha256, staged)
+  {
+    let size = std::fs::metadata(staged)?.len();
+    progress.report(Progress {
+      file: item.file.clone(),
+      downloaded: size,
+      total: Some(size),
+    });
+    return Ok(());
+  }
+
+  std::fs::create_dir_all(partial_dir)?;
+  let name = item
+    .sha256
+    .clone()
+    .unwrap_or_else(|| sha256_hex(item.url.as_bytes()));
+  let partial = partial_dir.join(format!("{name}.part"));
+  let report = |downloaded, total| {
+    progress.report(Progress {
+      file: item.file.clone(),
+      downloaded,
+      total,
+    })
+  };
+
+  let sha256 = mirrors.run(&item.url, |url| stream_to_file(url, &partial, &report))?;
+  if let Err(err) = verify_digest(&sha256, &item.url, item.sha256.as_deref()) {
+    let _ = std::fs::remove_file(&partial);
+    return Err(err);
+  }
+  std::fs::rename(&partial, staged)?;
+  if let (Some(cache), Some(sha256)) = (cache, &item.sha256) {
+    // the download is fine even if it can't be cached
+    let _ = cache.put(sha256, staged);
+  }
   Ok(())
 }
 
-pub async fn batch_download_to_file(items: Vec<(String, PathBuf)>) -> Result<()> {
-  let result = futures::future::join_all(items.iter().map(|(url, file)| download_to_file(url, file))).await;
+/// Download all items into a staging directory inside `root` and move them into place
+/// only if every download succeeded and the files together match the aggregate `checksum`.
+pub async fn batch_download_to_file(
+  mirrors: &Mirrors,
+  cache: Option<&Cache>,
+  root: &Path,
+  items: Vec<Download>,
+  checksum: Option<u32>,
+  progress: &Reporter,
+) -> Result<()> {
+  batch_download_staged(mirrors, cache, root, items, checksum, progress)
+    .await?
+    .commit()
+}
+
+/// Same as `batch_download_to_file`, but leaves committing the verified files to the caller.
+pub async fn batch_download_staged(
+  mirrors: &Mirrors,
+  cache: Option<&Cache>,
+  root: &Path,
+  items: Vec<Download>,
+  checksum: Option<u32>,
+  progress: &Reporter,
+) -> Result<Transaction> {
+  let partial_dir = root.join(PARTIAL_DIR);
+  let mut transaction = Transaction::new(root)?;
+  let staged: Vec<(&Download, PathBuf)> = items
+    .iter()
+    .map(|item| (item, transaction.stage(&item.file)))
+    .collect();
+  // files without a sha256 are cached by URL, the aggregate checksum vouches for them
+  let url_cache = cache.zip(checksum);
+  let mut hits: Vec<bool> = staged
+    .iter()
+    .map(|(item, file)| match url_cache {
+      Some((cache, checksum)) if item.sha256.is_none() && cache.get_by_url(&item.url, checksum, file) => {
+        let size = std::fs::metadata(file)
+          .map(|metadata| metadata.len())
+          .unwrap_or(0);
+        progress.report(Progress {
+          file: item.file.clone(),
+          downloaded: size,
+          total: Some(size),
+        });
+        true
+      }
+      _ => false,
+    })
+    .collect();
+  let result = futures::future::join_all(
+    staged
+      .iter()
+      .zip(&hits)
+      .filter(|(_, hit)| !**hit)
+      .map(|((item, file), _)| download_to_file(mirrors, cache, item, file, &partial_dir, progress)),
+  )
+  .await;
   for item in result {
     item?;
   }
-  Ok(())
+  if let Some(expected) = checksum {
+    let files = || staged.iter().map(|(_, file)| file.clone()).collect();
+    let mut actual = checksum_for_files(files())?;
+    if actual != expected && hits.contains(&true) {
+      // a damaged cache entry, its files are downloaded again
+      for ((item, file), hit) in staged.iter().zip(hits.iter_mut()).filter(|(_, hit)| **hit) {
+        if let Some((cache, checksum)) = url_cache {
+          cache.remove_by_url(&item.url, checksum);
+        }
+        download_to_file(mirrors, cache, item, file, &partial_dir, progress).await?;
+        *hit = false;
+      }
+      actual = checksum_for_files(files())?;
+    }
+    if actual != expected {
+      bail!("Integrity check failed: expected checksum {expected}, got {actual}");
+    }
+    if let Some((cache, checksum)) = url_cache {
+      for ((item, file), hit) in staged.iter().zip(&hits) {
+        if item.sha256.is_none() && !hit {
+          // the download is fine even if it can't be cached
+          let _ = cache.put_by_url(&item.url, checksum, file);
+        }
+      }
+    }
+  }
+  Ok(transaction)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_server::{Reply, TestServer};
+  use std::sync::{Arc, Mutex};
+
+  #[test]
+  fn sha256() {
+    let hash = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    assert!(verify_sha256(b"hello", "/hello", Some(hash)).is_ok());
+    assert!(verify_sha256(b"hello", "/hello", Some(&hash.to_uppercase())).is_ok());
+    assert!(verify_sha256(b"hell", "/hello", Some(hash)).is_err());
+    assert!(verify_sha256(b"hell", "/hello", None).is_ok());
+  }
+
+  #[test]
+  fn resumes_interrupted_download() {
+    const BODY: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    let server = TestServer::start(|request, index| match index {
+      0 => Reply {
+        cut: Some(10),
+        ..Reply::file(request, BODY)
+      },
+      _ => Reply::file(request, BODY),
+    });
+    let mirrors = Mirrors::new([server.url.clone()]).with_backoff(Duration::from_millis(1));
+    let dir = tempfile::tempdir().unwrap();
+    let item = Download {
+      url: "/file.bin".to_string(),
+      file: dir.path().join("file.bin"),
+      sha256: Some(sha256_hex(BODY)),
+    };
+    let reports = Arc::new(Mutex::new(vec![]));
+    let log = reports.clone();
+    let progress = Reporter::new(move |progress| log.lock().unwrap().push(progress));
+
+    futures::executor::block_on(download_to_file(
+      &mirrors,
+      None,
+      &item,
+      &item.file,
+      &dir.path().join(PARTIAL_DIR),
+      &progress,
+    ))
+    .unwrap();
+    assert_eq!(std::fs::read(&item.file).unwrap(), BODY);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].path, "/file.bin");
+    assert_eq!(requests[1].range, Some(10));
+    let last = reports.lock().unwrap().last().cloned().unwrap();
+    assert_eq!(last.downloaded, BODY.len() as u64);
+    assert_eq!(last.total, Some(BODY.len() as u64));
+  }
+
+  #[test]
+  fn misreported_range() {
+    const BODY: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    // the range is ignored, the whole file is sent as partial content
+    let server = TestServer::start(|request, _| match request.range {
+      Some(_) => Reply {
+        status: 206,
+        headers: vec![(
+          "Content-Range".to_string(),
+          format!("bytes 0-{}/{}", BODY.len() - 1, BODY.len()),
+        )],
+        ..Reply::ok(BODY)
+      },
+      None => Reply::ok(BODY),
+    });
+    let mirrors = Mirrors::new([server.url.clone()]);
+    let dir = tempfile::tempdir().unwrap();
+    let item = Download {
+      url: "/file.bin".to_string(),
+      file: dir.path().join("file.bin"),
+      sha256: Some(sha256_hex(BODY)),
+    };
+    std::fs::create_dir_all(dir.path().join(PARTIAL_DIR)).unwrap();
+    std::fs::write(
+      dir
+        .path()
+        .join(PARTIAL_DIR)
+        .join(format!("{}.part", sha256_hex(BODY))),
+      &BODY[..10],
+    )
+    .unwrap();
+
+    futures::executor::block_on(download_to_file(
+      &mirrors,
+      None,
+      &item,
+      &item.file,
+      &dir.path().join(PARTIAL_DIR),
+      &Reporter::default(),
+    ))
+    .unwrap();
+    assert_eq!(std::fs::read(&item.file).unwrap(), BODY);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].range, Some(10));
+    assert_eq!(requests[1].range, None);
+  }
+
+  #[test]
+  fn stale_partial_download() {
+    let server = TestServer::start(|request, _| Reply::file(request, b"new"));
+    let mirrors = Mirrors::new([server.url.clone()]);
+    let dir = tempfile::tempdir().unwrap();
+    let item = Download {
+      url: "/file.bin".to_string(),
+      file: dir.path().join("file.bin"),
+      sha256: Some(sha256_hex(b"new")),
+    };
+    std::fs::create_dir_all(dir.path().join(PARTIAL_DIR)).unwrap();
+    std::fs::write(
+      dir
+        .path()
+        .join(PARTIAL_DIR)
+        .join(format!("{}.part", sha256_hex(b"new"))),
+      "stale data",
+    )
+    .unwrap();
+
+    futures::executor::block_on(download_to_file(
+      &mirrors,
+      None,
+      &item,
+      &item.file,
+      &dir.path().join(PARTIAL_DIR),
+      &Reporter::default(),
+    ))
+    .unwrap();
+    assert_eq!(std::fs::read(&item.file).unwrap(), b"new");
+  }
+
+  #[test]
+  fn file_mirror() {
+    let mirror = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(mirror.path().join("data")).unwrap();
+    std::fs::write(mirror.path().join("data/file.bin"), "local").unwrap();
+    let unreachable = TestServer::start(|_, _| Reply::status(404));
+    let mirrors = Mirrors::new([unreachable.url.clone(), format!("file://{}", mirror.path().display())]);
+    let dir = tempfile::tempdir().unwrap();
+    let item = Download {
+      url: "/data/file.bin".to_string(),
+      file: dir.path().join("file.bin"),
+      sha256: Some(sha256_hex(b"local")),
+    };
+
+    futures::executor::block_on(download_to_file(
+      &mirrors,
+      None,
+      &item,
+      &item.file,
+      &dir.path().join(PARTIAL_DIR),
+      &Reporter::default(),
+    ))
+    .unwrap();
+    assert_eq!(std::fs::read(&item.file).unwrap(), b"local");
+  }
+
+  #[test]
+  fn cached_download() {
+    let server = TestServer::start(|request, _| Reply::file(request, b"shared"));
+    let mirrors = Mirrors::new([server.url.clone()]);
+    let dir = tempfile::tempdir().unwrap();
+    let cache = Cache::new(Some(dir.path().join("cache")), 1024);
+    // the same file for two game installations
+    for df in ["first", "second"] {
+      let item = Download {
+        url: "/data/file.bin".to_string(),
+        file: dir.path().join(df).join("file.bin"),
+        sha256: Some(sha256_hex(b"shared")),
+      };
+      std::fs::create_dir_all(dir.path().join(df)).unwrap();
+      futures::executor::block_on(download_to_file(
+        &mirrors,
+        Some(&cache),
+        &item,
+        &item.file,
+        &dir.path().join(df).join(PARTIAL_DIR),
+        &Reporter::default(),
+      ))
+      .unwrap();
+      assert_eq!(std::fs::read(&item.file).unwrap(), b"shared");
+    }
+    assert_eq!(server.requests().len(), 1);
+  }
+
+  #[test]
+  fn checksum_without_hashes() {
+    // manifests without per-file hashes are checked by the aggregate checksum only
+    let server = TestServer::start(|request, _| Reply::file(request, b"data"));
+    let mirrors = Mirrors::new([server.url.clone()]);
+    let dir = tempfile::tempdir().unwrap();
+    let item = Download {
+      url: "/data/file.bin".to_string(),
+      file: dir.path().join("file.bin"),
+      sha256: None,
+    };
+    let expected = {
+      std::fs::write(dir.path().join("expected.bin"), b"data").unwrap();
+      checksum_for_files(vec![dir.path().join("expected.bin")]).unwrap()
+    };
+
+    let download = |checksum| {
+      futures::executor::block_on(batch_download_to_file(
+        &mirrors,
+        None,
+        dir.path(),
+        vec![item.clone()],
+        Some(checksum),
+        &Reporter::default(),
+      ))
+    };
+    assert!(download(expected.wrapping_add(1)).is_err());
+    assert!(!item.file.exists());
+    download(expected).unwrap();
+    assert_eq!(std::fs::read(&item.file).unwrap(), b"data");
+  }
+
+  #[test]
+  fn cached_without_hashes() {
+    let server = TestServer::start(|request, _| Reply::file(request, b"shared"));
+    let mirrors = Mirrors::new([server.url.clone()]);
+    let dir = tempfile::tempdir().unwrap();
+    let cache = Cache::new(Some(dir.path().join("cache")), 1024);
+    std::fs::write(dir.path().join("expected.bin"), b"shared").unwrap();
+    let checksum = checksum_for_files(vec![dir.path().join("expected.bin")]).unwrap();
+    let download = |df: &str| {
+      let root = dir.path().join(df);
+      std::fs::create_dir_all(&root).unwrap();
+      let item = Download {
+        url: "/data/file.bin".to_string(),
+        file: root.join("file.bin"),
+        sha256: None,
+      };
+      futures::executor::block_on(batch_download_to_file(
+        &mirrors,
+        Some(&cache),
+        &root,
+        vec![item.clone()],
+        Some(checksum),
+        &Reporter::default(),
+      ))
+      .unwrap();
+      assert_eq!(std::fs::read(&item.file).unwrap(), b"shared");
+    };
+
+    download("first");
+    download("second");
+    assert_eq!(server.requests().len(), 1);
+
+    // a damaged entry fails the checksum and is downloaded again
+    for entry in std::fs::read_dir(dir.path().join("cache")).unwrap() {
+      std::fs::write(entry.unwrap().path(), b"damaged").unwrap();
+    }
+    download("third");
+    assert_eq!(server.requests().len(), 2);
+  }
+
+  #[test]
+  fn content_range() {
+    assert_eq!(content_range_total("bytes 100-199/200"), Some(200));
+    assert_eq!(content_range_total("bytes 100-199/*"), None);
+    assert_eq!(content_range_total("garbage"), None);
+    assert_eq!(content_range_start("bytes 100-199/200"), Some(100));
+    assert_eq!(content_range_start("bytes */200"), None);
+    assert_eq!(content_range_start("garbage"), None);
+  }
 }
@@ -1,6 +1,12 @@
 use anyhow::Result;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 
-use crate::fetch;
+use crate::{
+  df_binary::{DfBinary, OS},
+  fetch,
+  mirrors::{Mirrors, mirrors},
+};
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Manifest {
@@ -10,17 +16,46 @@ pub struct Manifest {
   pub config: String,
   pub offsets: String,
   pub dfhooks: String,
+  /// Optional SHA-256 of the files, keyed by field name (`lib`, `config`, `offsets`, `dfhooks`)
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub sha256: BTreeMap<String, String>,
+  /// DF version of the build, like `51.01`, empty in older manifests
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub version: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub os: Option<OS>,
+  /// Where the build is sold: `steam`, `itch` or `classic`
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub store: String,
 }
 
-#[derive(Default)]
+impl Manifest {
+  /// URLs of the files by field name, in the order of `Installer::hook_files`.
+  pub fn files(&self) -> [(&'static str, String); 4] {
+    [
+      ("lib", self.lib.clone()),
+      ("config", self.config.clone()),
+      ("offsets", self.offsets.clone()),
+      ("dfhooks", self.dfhooks.clone()),
+    ]
+  }
+}
+
+#[derive(Default, Clone)]
 pub struct HookMetadata {
   pub manifest: Manifest,
   pub vec_manifests: Vec<Manifest>,
+  /// Date of the local copy the manifests come from, `None` if they were just fetched
+  pub cached: Option<String>,
 }
 
 impl HookMetadata {
   pub async fn from_url(url: &str, pick_df_checksum: Option<u32>) -> Result<Self> {
-    let manifests: Vec<Manifest> = fetch::fetch_json(url)?;
+    Self::from_mirrors(&mirrors(), url, pick_df_checksum).await
+  }
+
+  pub async fn from_mirrors(mirrors: &Mirrors, url: &str, pick_df_checksum: Option<u32>) -> Result<Self> {
+    let (manifests, cached): (Vec<Manifest>, _) = fetch::fetch_signed_json_from(mirrors, url)?;
 
     let picked = match pick_df_checksum {
       Some(checksum) => {
@@ -36,6 +71,7 @@ impl HookMetadata {
     Ok(Self {
       manifest: picked,
       vec_manifests: manifests,
+      cached,
     })
   }
 
@@ -46,4 +82,78 @@ impl HookMetadata {
       self.manifest = Manifest::default();
     }
   }
+
+  /// Supported build closest in version to `bin` for the same OS and store, to tell whether
+  /// DF has to be updated or rolled back. A newer build wins over an equally distant older one.
+  pub fn nearest(&self, bin: &DfBinary) -> Option<&Manifest> {
+    if bin.version.is_empty() {
+      return None;
+    }
+    let position = |version: &str| {
+      let (major, minor, _) = DfBinary::version_key(version);
+      i64::from(major) * 1000 + i64::from(minor)
+    };
+    self
+      .vec_manifests
+      .iter()
+      .filter(|item| !item.version.is_empty() && item.os == Some(bin.os))
+      .filter(|item| item.store.is_empty() || (item.store == "steam") == bin.steam)
+      .min_by_key(|item| {
+        (
+          position(&item.version).abs_diff(position(&bin.version)),
+          Reverse(DfBinary::version_key(&item.version)),
+        )
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn manifest(df: u32, version: &str, os: OS, store: &str) -> Manifest {
+    Manifest {
+      df,
+      version: version.to_string(),
+      os: Some(os),
+      store: store.to_string(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn nearest_build() {
+    let metadata = HookMetadata {
+      vec_manifests: vec![
+        manifest(1, "50.13", OS::Windows, "steam"),
+        manifest(2, "51.01", OS::Windows, "steam"),
+        manifest(3, "51.03", OS::Windows, "steam"),
+        manifest(4, "51.02", OS::Windows, "itch"),
+        manifest(5, "51.02", OS::Linux, "steam"),
+        Manifest {
+          df: 6,
+          ..Default::default()
+        },
+      ],
+      ..Default::default()
+    };
+    let bin = |version: &str, steam: bool| DfBinary {
+      checksum: 42,
+      os: OS::Windows,
+      version: version.to_string(),
+      steam,
+      ..DfBinary::default()
+    };
+    // equally distant, the newer one
+    assert_eq!(metadata.nearest(&bin("51.02", true)).map(|item| item.df), Some(3));
+    assert_eq!(metadata.nearest(&bin("50.14", true)).map(|item| item.df), Some(1));
+    assert_eq!(metadata.nearest(&bin("52.01", true)).map(|item| item.df), Some(3));
+    assert_eq!(
+      metadata
+        .nearest(&bin("51.02-beta1", false))
+        .map(|item| item.df),
+      Some(4)
+    );
+    assert_eq!(metadata.nearest(&bin("", true)).map(|item| item.df), None);
+  }
 }
@@ -0,0 +1,206 @@
+use anyhow::Result;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use crate::{
+  agent::agent,
+  mirrors::{Mirrors, file_url_path, is_transient},
+  utils::sha256_hex,
+};
+
+static HTTP_CACHE: LazyLock<HttpCache> =
+  LazyLock::new(|| HttpCache::new(dirs::cache_dir().map(|dir| dir.join("dfint-installer").join("manifests"))));
+
+/// Cache of the manifests, shared by all game installations on this machine.
+pub fn http_cache() -> &'static HttpCache {
+  &HTTP_CACHE
+}
+
+/// Validators the server sent with a cached response.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Validators {
+  url: String,
+  etag: Option<String>,
+  last_modified: Option<String>,
+  /// When the response was received, RFC 3339
+  #[serde(default)]
+  stored: Option<String>,
+}
+
+/// Body of a response, possibly from the local copy.
+#[derive(Debug)]
+pub struct Fetched {
+  pub body: Vec<u8>,
+  /// When the local copy was stored, set if no mirror could be reached
+  pub cached: Option<String>,
+}
+
+/// Last responses of small files that are requested on every start, with their `ETag` and
+/// `Last-Modified` headers, so they are only downloaded again when they changed.
+///
+/// When no mirror can be reached, the cached copy of any mirror is used instead.
+pub struct HttpCache {
+  dir: Option<PathBuf>,
+}
+
+impl HttpCache {
+  pub fn new(dir: Option<PathBuf>) -> Self {
+    Self { dir }
+  }
+
+  /// Body and validators are stored next to each other, named by the hash of the URL.
+  fn paths(&self, url: &str) -> Option<(PathBuf, PathBuf)> {
+    let dir = self.dir.as_ref()?;
+    let name = sha256_hex(url.as_bytes());
+    Some((dir.join(&name), dir.join(format!("{name}.json"))))
+  }
+
+  fn load(&self, url: &str) -> Option<(Validators, Vec<u8>)> {
+    let (body, validators) = self.paths(url)?;
+    let validators: Validators = serde_json::from_str(&std::fs::read_to_string(validators).ok()?).ok()?;
+    if validators.url != url {
+      return None;
+    }
+    Some((validators, std::fs::read(body).ok()?))
+  }
+
+  fn store(&self, validators: &Validators, body: &[u8]) -> Result<()> {
+    let Some((body_path, validators_path)) = self.paths(&validators.url) else {
+      return Ok(());
+    };
+    std::fs::create_dir_all(
+      body_path
+        .parent()
+        .expect("cache files are in the cache dir"),
+    )?;
+    std::fs::write(body_path, body)?;
+    std::fs::write(validators_path, serde_json::to_string(validators)?)?;
+    Ok(())
+  }
+
+  /// Fetch `path` from the mirrors, sending the validators of the cached copy.
+  pub fn fetch(&self, mirrors: &Mirrors, path: &str) -> Result<Fetched> {
+    let result = mirrors.run(path, |url| {
+      if let Some(path) = file_url_path(url) {
+        return Ok(std::fs::read(path)?);
+      }
+      self.fetch_url(url)
+    });
+    match result {
+      Ok(body) => Ok(Fetched { body, cached: None }),
+      Err(err) if is_transient(&err) => mirrors
+        .urls()
+        .iter()
+        .find_map(|base| self.load(&format!("{base}{path}")))
+        .map(|(validators, body)| Fetched {
+          body,
+          cached: Some(validators.stored.unwrap_or_default()),
+        })
+        .ok_or(err),
+      Err(err) => Err(err),
+    }
+  }
+
+  fn fetch_url(&self, url: &str) -> Result<Vec<u8>> {
+    let cached = self.load(url);
+    let mut request = agent().get(url);
+    if let Some((validators, _)) = &cached {
+      if let Some(etag) = &validators.etag {
+        request = request.header("If-None-Match", etag);
+      }
+      if let Some(last_modified) = &validators.last_modified {
+        request = request.header("If-Modified-Since", last_modified);
+      }
+    }
+    let response = request.call()?;
+    if response.status() == 304
+      && let Some((_, body)) = cached
+    {
+      return Ok(body);
+    }
+
+    let header = |name: &str| {
+      response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+    };
+    let validators = Validators {
+      url: url.to_string(),
+      etag: header("etag"),
+      last_modified: header("last-modified"),
+      stored: Some(chrono::Local::now().to_rfc3339()),
+    };
+    let mut body = Vec::new();
+    response.into_body().into_reader().read_to_end(&mut body)?;
+    if validators.etag.is_some() || validators.last_modified.is_some() {
+      // a failure to cache only costs a download on the next start
+      let _ = self.store(&validators, &body);
+    }
+    Ok(body)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_server::{Reply, TestServer};
+  use std::time::Duration;
+
+  /// `body` tagged with `etag`, or 304 if the client has it already.
+  fn tagged(request: &crate::test_server::Request, etag: &str, body: &str) -> Reply {
+    let reply = match request.if_none_match.as_deref() == Some(etag) {
+      true => Reply::status(304),
+      false => Reply::ok(body),
+    };
+    Reply {
+      headers: vec![("ETag".to_string(), etag.to_string())],
+      ..reply
+    }
+  }
+
+  #[test]
+  fn conditional_requests() {
+    let server = TestServer::start(|request, index| match index {
+      0 | 1 => tagged(request, "\"v0\"", "first"),
+      _ => tagged(request, "\"v1\"", "second"),
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let cache = HttpCache::new(Some(dir.path().to_path_buf()));
+    let mirrors = Mirrors::new([server.url.clone()]);
+
+    for expected in ["first", "first", "second", "second"] {
+      let fetched = cache.fetch(&mirrors, "/manifest.json").unwrap();
+      assert_eq!(fetched.body, expected.as_bytes());
+      assert_eq!(fetched.cached, None);
+    }
+    let sent: Vec<_> = server
+      .requests()
+      .into_iter()
+      .map(|request| request.if_none_match)
+      .collect();
+    assert_eq!(
+      sent,
+      [None, Some("\"v0\""), Some("\"v0\""), Some("\"v1\"")].map(|etag| etag.map(str::to_string))
+    );
+  }
+
+  #[test]
+  fn unreachable_server_uses_cached_copy() {
+    let server = TestServer::start(|request, index| match index {
+      0 => tagged(request, "\"v0\"", "cached"),
+      _ => Reply::status(503),
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let cache = HttpCache::new(Some(dir.path().to_path_buf()));
+    let mirrors = Mirrors::new([server.url.clone()]).with_backoff(Duration::from_millis(1));
+
+    assert_eq!(cache.fetch(&mirrors, "/manifest.json").unwrap().cached, None);
+    let fetched = cache.fetch(&mirrors, "/manifest.json").unwrap();
+    assert_eq!(fetched.body, b"cached");
+    assert!(fetched.cached.is_some());
+    assert!(cache.fetch(&mirrors, "/other.json").is_err());
+  }
+}
@@ -0,0 +1,288 @@
+use memmap2::Mmap;
+use object::read::macho::{FatArch, MachOFatFile32, MachOFatFile64};
+use object::read::pe::{PeFile64, ResourceDirectoryEntryData, ResourceNameOrId};
+use object::{FileKind, LittleEndian as LE, Object, ObjectSection, ObjectSymbol, pe};
+use regex::bytes::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::df_binary::{DfBinary, DfBinaryError, OS};
+
+/// Import the Steam builds call on start.
+static STEAM_INIT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"SteamAPI_Init").unwrap());
+/// A whole version string, as found in the version resource.
+static VERSION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d+\.\d+(-[^\d]+\d*)?$").unwrap());
+
+/// What a single pass over a DF executable tells about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+  /// Platform of the binary format, PE, ELF or Mach-O
+  pub os: OS,
+  /// Link time of a Windows build, crc32 of the whole file of the other builds
+  pub checksum: u32,
+  pub version: Option<String>,
+  /// GNU build id of an ELF, CodeView GUID and age of a PE, UUID of a Mach-O, as hex
+  pub build_id: Option<String>,
+  pub steam: bool,
+}
+
+impl Identity {
+  /// Identify the executable at `path`, the file is mapped into memory instead of read.
+  pub fn of_file(path: &Path) -> Result<Self, DfBinaryError> {
+    let file = std::fs::File::open(path)?;
+    // SAFETY: the map is only read while it's alive, a file changed meanwhile by a game
+    // update can make the result wrong but is no memory error on the supported platforms
+    let map = unsafe { Mmap::map(&file)? };
+    Self::of(&map)
+  }
+
+  /// The OS is told by the format of the binary, not by its name.
+  pub fn of(data: &[u8]) -> Result<Self, DfBinaryError> {
+    match FileKind::parse(data) {
+      Ok(FileKind::Pe64) => Self::of_pe(data),
+      Ok(FileKind::Pe32) => Err(DfBinaryError::UnsupportedPe32),
+      Ok(FileKind::Elf32 | FileKind::Elf64) => Self::of_elf(data),
+      Ok(FileKind::MachO32 | FileKind::MachO64) => Self::of_macho(data, data),
+      Ok(FileKind::MachOFat32) => Self::of_fat(
+        data,
+        MachOFatFile32::parse(data).map(|fat| fat.arches().first().map(|arch| arch.data(data))),
+      ),
+      Ok(FileKind::MachOFat64) => Self::of_fat(
+        data,
+        MachOFatFile64::parse(data).map(|fat| fat.arches().first().map(|arch| arch.data(data))),
+      ),
+      _ if data.starts_with(b"MZ") => Err(DfBinaryError::CorruptHeader("no PE header".to_string())),
+      _ if data.starts_with(b"\x7fELF") => Err(DfBinaryError::CorruptHeader("truncated ELF header".to_string())),
+      _ => Err(DfBinaryError::NotDfExecutable),
+    }
+  }
+
+  /// The version resource and `.rdata` are read, the checksum is the link time.
+  fn of_pe(data: &[u8]) -> Result<Self, DfBinaryError> {
+    let file = PeFile64::parse(data).map_err(|err| DfBinaryError::CorruptHeader(err.to_string()))?;
+    let read_only = sections(&file, &[".rdata", ".idata"]);
+    let version = version_resource(&file, data).or_else(|| {
+      read_only
+        .iter()
+        .filter_map(|section| DfBinary::detect_df_version(section))
+        .max_by_key(|version| DfBinary::version_key(version))
+    });
+    Ok(Self {
+      os: OS::Windows,
+      checksum: file.nt_headers().file_header.time_date_stamp.get(LE),
+      version,
+      build_id: file.pdb_info().ok().flatten().map(|info| {
+        let mut id = hex(&info.guid());
+        id.push_str(&format!("{:x}", info.age()));
+        id
+      }),
+      steam: read_only.iter().any(|section| STEAM_INIT.is_match(section)),
+    })
+  }
+
+  /// `.rodata` and `.dynstr` are read, files without them are searched as a whole.
+  fn of_elf(data: &[u8]) -> Result<Self, DfBinaryError> {
+    let file = object::File::parse(data).map_err(|err| DfBinaryError::CorruptHeader(err.to_string()))?;
+    let section = |name: &str| {
+      file
+        .section_by_name(name)
+        .and_then(|section| section.data().ok())
+        .unwrap_or(data)
+    };
+    Ok(Self {
+      os: OS::Linux,
+      checksum: crc32fast::hash(data),
+      version: DfBinary::detect_df_version(section(".rodata")),
+      build_id: file.build_id().ok().flatten().map(hex),
+      steam: STEAM_INIT.is_match(section(".dynstr")),
+    })
+  }
+
+  /// The first architecture of a universal binary is read, the checksum covers all of them.
+  fn of_fat(data: &[u8], slice: object::Result<Option<object::Result<&[u8]>>>) -> Result<Self, DfBinaryError> {
+    match slice {
+      Ok(Some(Ok(slice))) => Self::of_macho(data, slice),
+      Ok(None) => Err(DfBinaryError::CorruptHeader(
+        "universal binary without architectures".to_string(),
+      )),
+      Ok(Some(Err(err))) | Err(err) => Err(DfBinaryError::CorruptHeader(err.to_string())),
+    }
+  }
+
+  /// The C strings and the symbol table of the `slice` of `data` are read.
+  fn of_macho(data: &[u8], slice: &[u8]) -> Result<Self, DfBinaryError> {
+    let file = object::File::parse(slice).map_err(|err| DfBinaryError::CorruptHeader(err.to_string()))?;
+    let version = ["__cstring", "__const"]
+      .iter()
+      .filter_map(|name| file.section_by_name(name)?.data().ok())
+      .filter_map(DfBinary::detect_df_version)
+      .max_by_key(|version| DfBinary::version_key(version));
+    Ok(Self {
+      os: OS::MacOS,
+      checksum: crc32fast::hash(data),
+      version,
+      build_id: file.mach_uuid().ok().flatten().map(|uuid| hex(&uuid)),
+      steam: file.symbols().any(|symbol| {
+        symbol
+          .name_bytes()
+          .is_ok_and(|name| STEAM_INIT.is_match(name))
+      }),
+    })
+  }
+}
+
+fn sections<'data>(file: &PeFile64<'data>, names: &[&str]) -> Vec<&'data [u8]> {
+  file
+    .sections()
+    .filter(|section| section.name().is_ok_and(|name| names.contains(&name)))
+    .filter_map(|section| section.data().ok())
+    .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// `ProductVersion` of the first `RT_VERSION` resource, if it's a DF version string.
+fn version_resource(file: &PeFile64, data: &[u8]) -> Option<String> {
+  let directory = file
+    .data_directories()
+    .resource_directory(data, &file.section_table())
+    .ok()??;
+  let mut entry = directory
+    .root()
+    .ok()?
+    .entries
+    .iter()
+    .find(|entry| matches!(entry.name_or_id(), ResourceNameOrId::Id(id) if id == pe::RT_VERSION))?
+    .data(directory)
+    .ok()?;
+  // name and language levels, the first one is taken
+  let resource = loop {
+    match entry {
+      ResourceDirectoryEntryData::Table(table) => entry = table.entries.first()?.data(directory).ok()?,
+      ResourceDirectoryEntryData::Data(resource) => break resource,
+    }
+  };
+  let blob = file
+    .section_table()
+    .pe_data_at(data, resource.offset_to_data.get(LE))?
+    .get(..resource.size.get(LE) as usize)?;
+  version_string(blob, "ProductVersion")
+    .map(|version| version.trim().to_string())
+    .filter(|version| VERSION.is_match(version.as_bytes()))
+}
+
+/// Value of the string `key` in a `VS_VERSIONINFO` tree. Every node is its length, the
+/// length of its value, its type, a UTF-16 key, the value and its children, all but the
+/// first three aligned to 32 bits.
+fn version_string(node: &[u8], key: &str) -> Option<String> {
+  let word = |offset: usize| Some(u16::from_le_bytes(node.get(offset..offset + 2)?.try_into().ok()?));
+  let align = |offset: usize| (offset + 3) & !3;
+  let length = (word(0)? as usize).min(node.len());
+  let value_length = word(2)? as usize;
+  let text = word(4)? == 1;
+
+  let mut name = vec![];
+  let mut offset = 6;
+  loop {
+    let char = word(offset)?;
+    offset += 2;
+    if char == 0 {
+      break;
+    }
+    name.push(char);
+  }
+  offset = align(offset);
+  if text && String::from_utf16_lossy(&name) == key {
+    let value: Vec<u16> = (0..value_length)
+      .map_while(|index| word(offset + 2 * index))
+      .take_while(|char| *char != 0)
+      .collect();
+    return Some(String::from_utf16_lossy(&value));
+  }
+
+  // text values are counted in UTF-16 units
+  offset = align(offset + if text { value_length * 2 } else { value_length });
+  while offset + 6 <= length {
+    let child_length = word(offset)? as usize;
+    if child_length == 0 {
+      break;
+    }
+    let end = (offset + child_length).min(length);
+    if let Some(value) = version_string(&node[offset..end], key) {
+      return Some(value);
+    }
+    offset = align(end);
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/binaries");
+
+  #[test]
+  fn linux_build() {
+    let path = Path::new(FIXTURES).join("dwarfort");
+    let identity = Identity::of_file(&path).unwrap();
+    assert_eq!(identity.os, OS::Linux);
+    assert_eq!(identity.version.as_deref(), Some("51.01"));
+    assert!(identity.steam);
+    assert_eq!(
+      identity.build_id.as_deref(),
+      Some("7d0bd14a8ba64828bc42ebff5932bdc7e40d1c06")
+    );
+    assert_eq!(identity.checksum, crc32fast::hash(&std::fs::read(&path).unwrap()));
+  }
+
+  #[test]
+  fn windows_build() {
+    let identity = Identity::of_file(&Path::new(FIXTURES).join("Dwarf Fortress.exe")).unwrap();
+    assert_eq!(identity.os, OS::Windows);
+    assert_eq!(identity.checksum, 0x6512abcd);
+    // the version resource wins over the strings in .rdata
+    assert_eq!(identity.version.as_deref(), Some("51.01"));
+    assert!(identity.steam);
+    assert_eq!(identity.build_id, None);
+  }
+
+  #[test]
+  fn macos_build() {
+    let path = Path::new(FIXTURES).join("Dwarf Fortress.app/Contents/MacOS/dwarfort");
+    let data = std::fs::read(&path).unwrap();
+    let identity = Identity::of_file(&path).unwrap();
+    assert_eq!(identity.os, OS::MacOS);
+    assert_eq!(identity.version.as_deref(), Some("51.01"));
+    assert!(identity.steam);
+    assert_eq!(identity.build_id.as_deref(), Some("3f1c5e2a9b7d4e0f8a6b2c1d0e9f8a7b"));
+    assert_eq!(identity.checksum, crc32fast::hash(&data));
+
+    // the x86_64 slice on its own
+    let thin = Identity::of(&data[0x1000..]).unwrap();
+    assert_eq!(thin.version, identity.version);
+    assert_eq!(thin.checksum, crc32fast::hash(&data[0x1000..]));
+  }
+
+  #[test]
+  fn other_files() {
+    assert!(matches!(
+      Identity::of(b"\x0050.14\x00"),
+      Err(DfBinaryError::NotDfExecutable)
+    ));
+    assert!(matches!(
+      Identity::of(b"\x7fELF\x0050.14\x00"),
+      Err(DfBinaryError::CorruptHeader(_))
+    ));
+    assert!(matches!(
+      Identity::of(b"MZ\x0050.14\x00"),
+      Err(DfBinaryError::CorruptHeader(_))
+    ));
+    assert!(matches!(
+      Identity::of(b"\xcf\xfa\xed\xfe\x07\x00\x00\x01\x03\x00\x00\x00\x02\x00\x00\x00"),
+      Err(DfBinaryError::CorruptHeader(_))
+    ));
+  }
+}
@@ -0,0 +1,796 @@
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+  backup::Backup,
+  bundle::{Bundle, Contents},
+  cache::cache,
+  constants::*,
+  df_binary::DfBinary,
+  dict_metadata::DictMetadata,
+  fetch::{Download, PARTIAL_DIR, batch_download_staged},
+  hook_metadata::HookMetadata,
+  mirrors::mirrors,
+  progress::Reporter,
+  receipt::{FileRecord, Receipt, Record},
+  utils::checksum_for_files,
+  verify::{FileReport, FileState, Report},
+};
+
+pub const NO_LANGUAGE: &str = "None";
+
+static ALLOW_UNHASHED: AtomicBool = AtomicBool::new(false);
+
+/// Whether files the signed manifest has no SHA-256 for may be installed, checked by the crc32 only.
+pub fn allow_unhashed() -> bool {
+  ALLOW_UNHASHED.load(Ordering::Relaxed)
+}
+
+pub fn set_allow_unhashed(allow: bool) {
+  ALLOW_UNHASHED.store(allow, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookState {
+  NotLoaded,
+  Unsupported,
+  UpToDate,
+  UpdateAvailable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DictState {
+  NotLoaded,
+  NotSelected,
+  UpToDate,
+  UpdateAvailable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+  /// Hook files were written, carries the new local checksum
+  HookUpdated(u32),
+  /// Dictionary files were written, carries the new local checksum
+  DictUpdated(u32),
+  HookRemoved,
+  OldDataRemoved,
+  OriginalRestored,
+}
+
+#[derive(Clone, Copy)]
+enum Part {
+  Hook,
+  Dict,
+}
+
+/// Install engine: a DF binary together with the metadata of the hook and the dictionary.
+pub struct Installer {
+  pub bin: DfBinary,
+  pub hook_metadata: HookMetadata,
+  pub dict_metadata: DictMetadata,
+  pub language: String,
+  pub hook_checksum: u32,
+  pub dict_checksum: u32,
+  /// Whether there is a backup of the files the installer has overwritten
+  pub backed_up: bool,
+  pub receipt: Option<Receipt>,
+  /// Installed files compared with the receipt
+  pub report: Report,
+  /// Don't overwrite installed files that were modified locally
+  pub keep_local_changes: bool,
+  /// Receives the download progress of the update jobs
+  pub progress: Reporter,
+  /// Offline bundle the metadata was read from, updates install the files from it
+  pub bundle: Option<Arc<Bundle>>,
+}
+
+impl Default for Installer {
+  fn default() -> Self {
+    Self {
+      bin: DfBinary::default(),
+      hook_metadata: HookMetadata::default(),
+      dict_metadata: DictMetadata::default(),
+      language: NO_LANGUAGE.to_string(),
+      hook_checksum: 0,
+      dict_checksum: 0,
+      backed_up: false,
+      receipt: None,
+      report: Report::default(),
+      keep_local_changes: false,
+      progress: Reporter::default(),
+      bundle: None,
+    }
+  }
+}
+
+impl Installer {
+  pub fn new(bin: DfBinary, language: String, hook_metadata: HookMetadata, dict_metadata: DictMetadata) -> Self {
+    let mut installer = Self {
+      bin,
+      language,
+      ..Default::default()
+    };
+    installer.set_hook_metadata(hook_metadata);
+    installer.set_dict_metadata(dict_metadata);
+    installer.refresh();
+    installer
+  }
+
+  /// Date of the local copy of the manifests if they couldn't be fetched.
+  pub fn offline_since(&self) -> Option<&str> {
+    self
+      .hook_metadata
+      .cached
+      .as_deref()
+      .or(self.dict_metadata.cached.as_deref())
+  }
+
+  pub fn set_binary(&mut self, bin: DfBinary) {
+    self.bin = bin;
+    self.hook_metadata.pick_df_checksum(self.bin.checksum);
+    self.refresh();
+  }
+
+  pub fn set_hook_metadata(&mut self, metadata: HookMetadata) {
+    self.hook_metadata = metadata;
+    self.hook_metadata.pick_df_checksum(self.bin.checksum);
+  }
+
+  pub fn set_dict_metadata(&mut self, metadata: DictMetadata) {
+    self.dict_metadata = metadata;
+    if self.language != NO_LANGUAGE {
+      self
+        .dict_metadata
+        .pick_language_by_name(self.language.clone());
+    }
+  }
+
+  /// Install from an offline bundle with the metadata read from it.
+  pub fn set_bundle(&mut self, bundle: Bundle, hook_metadata: HookMetadata, dict_metadata: DictMetadata) {
+    self.set_hook_metadata(hook_metadata);
+    self.set_dict_metadata(dict_metadata);
+    // exported bundles carry a single language
+    if let Some(contents) = bundle.contents() {
+      self.select_language(&contents.language);
+    }
+    self.bundle = Some(Arc::new(bundle));
+  }
+
+  /// Installer for another DF installation with the same metadata, keeping the language
+  /// installed there.
+  pub fn for_binary(&self, bin: DfBinary) -> Self {
+    let mut installer = Self::new(
+      bin,
+      self.language.clone(),
+      self.hook_metadata.clone(),
+      self.dict_metadata.clone(),
+    );
+    if let Some(language) = installer
+      .receipt
+      .as_ref()
+      .and_then(|receipt| receipt.dictionary.as_ref())
+      .and_then(|record| record.language.clone())
+    {
+      installer.select_language(&language);
+    }
+    installer.keep_local_changes = self.keep_local_changes;
+    installer.progress = self.progress.clone();
+    installer.bundle = self.bundle.clone();
+    installer
+  }
+
+  /// Recompute checksums of the files present on disk.
+  pub fn refresh(&mut self) {
+    self.hook_checksum = self.local_hook_checksum().unwrap_or(0);
+    self.dict_checksum = self.local_dict_checksum().unwrap_or(0);
+    self.backed_up = self.bin.valid && Backup::new(&self.bin.dir).has_original();
+    self.receipt = if self.bin.valid {
+      Receipt::load(&self.bin.dir)
+    } else {
+      None
+    };
+    self.report = Report::new(&self.bin.dir, self.receipt.as_ref());
+  }
+
+  /// Select the dictionary by language name or code, returns the picked language name.
+  pub fn select_language(&mut self, language: &str) -> Option<String> {
+    let manifest = self.dict_metadata.vec_manifests.iter().find(|item| {
+      item.language.eq_ignore_ascii_case(language)
+        || item
+          .code
+          .as_ref()
+          .is_some_and(|code| code.eq_ignore_ascii_case(language))
+    })?;
+    self.language = manifest.language.clone();
+    self.dict_metadata.manifest = manifest.clone();
+    Some(self.language.clone())
+  }
+
+  /// Whether the files of `record` were installed from a manifest with `checksum` and are intact,
+  /// apart from local changes the user chose to keep.
+  fn installed_from(&self, record: Option<&Record>, checksum: u32) -> bool {
+    record.is_some_and(|record| {
+      record.checksum == checksum
+        && record.files.iter().all(|file| {
+          matches!(
+            self.report.state(&file.path),
+            Some(FileState::Unchanged | FileState::Kept)
+          )
+        })
+    })
+  }
+
+  pub fn hook_state(&self) -> HookState {
+    let manifest = &self.hook_metadata.manifest;
+    let receipt = self
+      .receipt
+      .as_ref()
+      .and_then(|receipt| receipt.hook.as_ref());
+    match (
+      manifest.df == self.bin.checksum,
+      manifest.checksum == self.hook_checksum || self.installed_from(receipt, manifest.checksum),
+      manifest.checksum == 0,
+      self.hook_metadata.vec_manifests.is_empty(),
+    ) {
+      (_, _, true, true) => HookState::NotLoaded,
+      (false, _, _, _) => HookState::Unsupported,
+      (true, true, _, _) => HookState::UpToDate,
+      (true, false, _, _) => HookState::UpdateAvailable,
+    }
+  }
+
+  pub fn dict_state(&self) -> DictState {
+    let manifest = &self.dict_metadata.manifest;
+    let receipt = self
+      .receipt
+      .as_ref()
+      .and_then(|receipt| receipt.dictionary.as_ref());
+    match (
+      manifest.checksum == self.dict_checksum || self.installed_from(receipt, manifest.checksum),
+      self.language == NO_LANGUAGE,
+      self.dict_metadata.vec_manifests.is_empty(),
+    ) {
+      (_, _, true) => DictState::NotLoaded,
+      (_, true, false) => DictState::NotSelected,
+      (true, false, false) => DictState::UpToDate,
+      (false, false, false) => DictState::UpdateAvailable,
+    }
+  }
+
+  pub fn update_available(&self) -> bool {
+    self.hook_state() == HookState::UpdateAvailable || self.dict_state() == DictState::UpdateAvailable
+  }
+
+  /// Whether any localization files are present in the DF directory.
+  pub fn installed(&self) -> bool {
+    self.hook_checksum != NO_HOOK_CHECKSUM || self.dict_checksum != NO_DICT_CHECKSUM
+  }
+
+  pub fn hook_files(&self) -> Vec<PathBuf> {
+    vec![
+      self.bin.lib_path.clone(),
+      self.bin.dir.join(PATH_CONFIG),
+      self.bin.dir.join(PATH_OFFSETS),
+      self.bin.dfhooks_path.clone(),
+    ]
+  }
+
+  pub fn dict_files(&self) -> Vec<PathBuf> {
+    vec![
+      self.bin.dir.join(PATH_DICT),
+      self.bin.dir.join(PATH_FONT),
+      self.bin.dir.join(PATH_ENCODING),
+    ]
+  }
+
+  /// Installed hook files that are missing or were modified.
+  pub fn hook_changes(&self) -> Vec<&FileReport> {
+    self.report.changed(&self.relative(&self.hook_files()))
+  }
+
+  /// Installed dictionary files that are missing or were modified.
+  pub fn dict_changes(&self) -> Vec<&FileReport> {
+    self.report.changed(&self.relative(&self.dict_files()))
+  }
+
+  /// Installed files that were modified locally and are about to be updated.
+  pub fn local_changes(&self) -> Vec<&FileReport> {
+    let mut files = vec![];
+    if self.hook_state() == HookState::UpdateAvailable {
+      files.extend(self.hook_changes());
+    }
+    if self.dict_state() == DictState::UpdateAvailable {
+      files.extend(self.dict_changes());
+    }
+    files.retain(|file| file.state == FileState::Modified);
+    files
+  }
+
+  fn relative(&self, files: &[PathBuf]) -> Vec<PathBuf> {
+    files
+      .iter()
+      .filter_map(|file| file.strip_prefix(&self.bin.dir).ok())
+      .map(Path::to_path_buf)
+      .collect()
+  }
+
+  /// Absolute paths of the local changes to keep on update.
+  fn kept_files(&self) -> Vec<PathBuf> {
+    if !self.keep_local_changes {
+      return vec![];
+    }
+    self
+      .local_changes()
+      .iter()
+      .map(|file| self.bin.dir.join(&file.path))
+      .collect()
+  }
+
+  pub fn local_hook_checksum(&self) -> Result<u32> {
+    checksum_for_files(self.hook_files())
+  }
+
+  pub fn local_dict_checksum(&self) -> Result<u32> {
+    checksum_for_files(self.dict_files())
+  }
+
+  /// Job downloading the hook, `None` if the hook is up-to-date or not supported.
+  pub fn hook_update(&self) -> Option<impl Future<Output = Result<Event>> + Send + 'static> {
+    if self.hook_state() != HookState::UpdateAvailable {
+      return None;
+    }
+    let manifest = self.hook_metadata.manifest.clone();
+    let files = self.hook_files();
+    let items = Self::downloads(&files, manifest.files(), &manifest.sha256);
+    let record = Record {
+      checksum: manifest.checksum,
+      df: Some(manifest.df),
+      ..Default::default()
+    };
+    Some(Self::download_job(
+      self.bin.dir.clone(),
+      items,
+      self.kept_files(),
+      Part::Hook,
+      record,
+      self.progress.clone(),
+      self.bundle.clone(),
+    ))
+  }
+
+  /// Job downloading the dictionary, `None` if it is up-to-date or no language is selected.
+  pub fn dict_update(&self) -> Option<impl Future<Output = Result<Event>> + Send + 'static> {
+    if self.dict_state() != DictState::UpdateAvailable {
+      return None;
+    }
+    let manifest = self.dict_metadata.manifest.clone();
+    let files = self.dict_files();
+    let items = Self::downloads(&files, manifest.files(), &manifest.sha256);
+    let record = Record {
+      checksum: manifest.checksum,
+      language: Some(manifest.language),
+      ..Default::default()
+    };
+    Some(Self::download_job(
+      self.bin.dir.clone(),
+      items,
+      self.kept_files(),
+      Part::Dict,
+      record,
+      self.progress.clone(),
+      self.bundle.clone(),
+    ))
+  }
+
+  fn downloads<const N: usize>(
+    files: &[PathBuf],
+    urls: [(&str, String); N],
+    sha256: &BTreeMap<String, String>,
+  ) -> Vec<Download> {
+    files
+      .iter()
+      .zip(urls)
+      .map(|(file, (field, url))| Download {
+        url,
+        file: file.clone(),
+        sha256: sha256.get(field).cloned(),
+      })
+      .collect()
+  }
+
+  /// Whether the picked manifests lack a SHA-256 for any of their files.
+  pub fn unhashed(&self) -> bool {
+    let hook = &self.hook_metadata.manifest;
+    let dict = &self.dict_metadata.manifest;
+    hook
+      .files()
+      .iter()
+      .any(|(field, _)| !hook.sha256.contains_key(*field))
+      || dict
+        .files()
+        .iter()
+        .any(|(field, _)| !dict.sha256.contains_key(*field))
+  }
+
+  pub fn can_export_bundle(&self) -> bool {
+    !matches!(self.hook_state(), HookState::NotLoaded | HookState::Unsupported)
+      && !matches!(self.dict_state(), DictState::NotLoaded | DictState::NotSelected)
+  }
+
+  /// Job packing the current hook and dictionary into an offline bundle at `path`,
+  /// `None` if the DF version is not supported or no language is selected.
+  pub fn export_bundle(&self, path: PathBuf) -> Option<impl Future<Output = Result<Contents>> + Send + 'static> {
+    if !self.can_export_bundle() {
+      return None;
+    }
+    Some(Bundle::export(
+      path,
+      self.hook_metadata.manifest.clone(),
+      self.dict_metadata.manifest.clone(),
+      self.progress.clone(),
+    ))
+  }
+
+  /// Download `items` except for the `kept` files, back up what is replaced and record the install.
+  async fn download_job(
+    df_dir: PathBuf,
+    items: Vec<Download>,
+    kept: Vec<PathBuf>,
+    part: Part,
+    mut record: Record,
+    progress: Reporter,
+    bundle: Option<Arc<Bundle>>,
+  ) -> Result<Event> {
+    // manifests are signed, the per-file hashes extend the signature to the downloaded files
+    if let Some(item) = items.iter().find(|item| item.sha256.is_none()) {
+      if !allow_unhashed() {
+        bail!(
+          "Manifest has no SHA-256 for {}, refusing to install it. Allow files checked by the crc32 only in the settings or with --allow-unhashed",
+          item.url
+        );
+      }
+      if !kept.is_empty() {
+        bail!(
+          "Manifest has no SHA-256 for {} and the crc32 can't be checked while local changes are kept",
+          item.url
+        );
+      }
+    }
+    let downloads: Vec<Download> = items
+      .iter()
+      .filter(|item| !kept.contains(&item.file))
+      .cloned()
+      .collect();
+    // the aggregate checksum can't match with local changes in place
+    let checksum = kept.is_empty().then_some(record.checksum);
+    let replaced: Vec<PathBuf> = downloads.iter().map(|item| item.file.clone()).collect();
+    let mirrors = match &bundle {
+      Some(bundle) => bundle.mirrors(),
+      None => &mirrors(),
+    };
+    let transaction = batch_download_staged(
+      mirrors,
+      Some(&cache()),
+      &df_dir.join(PATH_DATA),
+      downloads,
+      checksum,
+      &progress,
+    )
+    .await?;
+    Backup::new(&df_dir).snapshot(&replaced)?;
+    transaction.commit()?;
+
+    record.installed = chrono::Local::now().to_rfc3339();
+    for item in &items {
+      let path = item.file.strip_prefix(&df_dir)?;
+      let mut file = FileRecord::new(&df_dir, path, &item.url)?;
+      file.kept = kept.contains(&item.file);
+      record.files.push(file);
+    }
+    let files: Vec<PathBuf> = items.iter().map(|item| item.file.clone()).collect();
+    Receipt::update(&df_dir, |receipt| match part {
+      Part::Hook => receipt.hook = Some(record),
+      Part::Dict => receipt.dictionary = Some(record),
+    })?;
+
+    let checksum = checksum_for_files(files)?;
+    Ok(match part {
+      Part::Hook => Event::HookUpdated(checksum),
+      Part::Dict => Event::DictUpdated(checksum),
+    })
+  }
+
+  /// Hook and dictionary jobs for everything that has an update.
+  pub async fn update_all(&self) -> Result<Vec<Event>> {
+    let mut events = vec![];
+    if let Some(job) = self.hook_update() {
+      events.push(job.await?);
+    }
+    if let Some(job) = self.dict_update() {
+      events.push(job.await?);
+    }
+    Ok(events)
+  }
+
+  /// Update the installations one after another, so the later ones get the files from the download cache.
+  pub async fn update_each(installers: Vec<Installer>) -> Vec<(PathBuf, Result<Vec<Event>>)> {
+    let mut results = vec![];
+    for installer in installers {
+      let result = installer.update_all().await;
+      results.push((installer.bin.path.clone(), result));
+    }
+    results
+  }
+
+  pub fn apply(&mut self, event: &Event) {
+    match event {
+      Event::HookUpdated(checksum) => {
+        self.refresh();
+        self.hook_checksum = *checksum;
+      }
+      Event::DictUpdated(checksum) => {
+        self.refresh();
+        self.dict_checksum = *checksum;
+      }
+      Event::HookRemoved | Event::OldDataRemoved | Event::OriginalRestored => self.refresh(),
+    }
+  }
+
+  fn data_dir(&self) -> PathBuf {
+    self.bin.dir.join(PATH_DATA)
+  }
+
+  pub fn old_data_exists(&self) -> bool {
+    if !self.bin.valid {
+      return false;
+    }
+    self.bin.dir.join("dfint_launcher.exe").exists() || self.bin.dir.join("dfint_data").exists()
+  }
+
+  pub fn remove_old_data(&self) -> Event {
+    let _ = std::fs::remove_file(self.bin.dir.join("dfint_launcher.exe"));
+    let _ = std::fs::remove_dir_all(self.bin.dir.join("dfint_data"));
+    Event::OldDataRemoved
+  }
+
+  /// Put back every file the installer has overwritten or created, as it was before the first install.
+  pub fn restore_original(&self) -> Result<Event> {
+    Backup::new(&self.bin.dir).restore_original()?;
+    Receipt::delete(&self.bin.dir);
+    let _ = std::fs::remove_dir_all(self.data_dir().join(PARTIAL_DIR));
+    let _ = std::fs::remove_dir(self.data_dir());
+    Ok(Event::OriginalRestored)
+  }
+
+  /// Delete the installed files listed in the receipt, files that existed before the installer
+  /// overwrote them are restored from the backup. Without a receipt the known file set is deleted.
+  pub fn remove_hook_data(&self) -> Event {
+    let Some(receipt) = Receipt::load(&self.bin.dir) else {
+      let _ = std::fs::write(self.bin.dir.join(PATH_FONT), ORIGINAL_FONT);
+      let _ = std::fs::remove_file(self.bin.lib_path.clone());
+      let _ = std::fs::remove_dir_all(self.data_dir());
+      return Event::HookRemoved;
+    };
+
+    let backup = Backup::new(&self.bin.dir);
+    for file in receipt.files() {
+      if backup.restore(&file.path).unwrap_or(false) {
+        continue;
+      }
+      let target = self.bin.dir.join(&file.path);
+      if file.path == Path::new(PATH_FONT) {
+        let _ = std::fs::write(target, ORIGINAL_FONT);
+      } else {
+        let _ = std::fs::remove_file(target);
+      }
+    }
+    Receipt::delete(&self.bin.dir);
+    let _ = std::fs::remove_dir_all(self.data_dir().join(PARTIAL_DIR));
+    let _ = std::fs::remove_dir(self.data_dir());
+    Event::HookRemoved
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{df_binary::OS, dict_metadata::Manifest as DictManifest, hook_metadata::Manifest as HookManifest};
+
+  fn installer(dir: &std::path::Path) -> Installer {
+    std::fs::create_dir_all(dir.join("data/art")).unwrap();
+    std::fs::write(dir.join(PATH_FONT), ORIGINAL_FONT).unwrap();
+    let bin = DfBinary {
+      path: dir.join("dwarfort"),
+      dir: dir.to_path_buf(),
+      checksum: 42,
+      os: OS::Linux,
+      version: "51.01".to_string(),
+      build_id: None,
+      steam: false,
+      compat: None,
+      valid: true,
+      lib_path: dir.join("libdfhooks_dfint.so"),
+      dfhooks_path: dir.join("libdfhooks.so"),
+    };
+    let hook = HookManifest {
+      df: 42,
+      checksum: 1,
+      ..Default::default()
+    };
+    let dict = DictManifest {
+      language: "Russian".to_string(),
+      checksum: 2,
+      code: Some("ru".to_string()),
+      ..Default::default()
+    };
+    Installer::new(
+      bin,
+      NO_LANGUAGE.to_string(),
+      HookMetadata {
+        manifest: hook.clone(),
+        vec_manifests: vec![hook],
+        cached: None,
+      },
+      DictMetadata {
+        manifest: DictManifest::default(),
+        vec_manifests: vec![dict],
+        cached: None,
+      },
+    )
+  }
+
+  #[test]
+  fn unhashed_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let item = Download {
+      url: "/data/file.bin".to_string(),
+      file: dir.path().join("file.bin"),
+      sha256: None,
+    };
+    let job = |kept: Vec<PathBuf>| {
+      futures::executor::block_on(Installer::download_job(
+        dir.path().to_path_buf(),
+        vec![item.clone()],
+        kept,
+        Part::Hook,
+        Record::default(),
+        Reporter::default(),
+        None,
+      ))
+    };
+    let err = job(vec![]).unwrap_err();
+    assert!(err.to_string().contains("refusing to install"));
+    // the crc32 is the only check left, it is lost with local changes
+    set_allow_unhashed(true);
+    let err = job(vec![dir.path().join("other.bin")]).unwrap_err();
+    set_allow_unhashed(false);
+    assert!(err.to_string().contains("local changes are kept"));
+    assert!(!item.file.exists());
+  }
+
+  #[test]
+  fn empty_dir_has_nothing_installed() {
+    let dir = tempfile::tempdir().unwrap();
+    let installer = installer(dir.path());
+    assert_eq!(installer.dict_checksum, NO_DICT_CHECKSUM);
+    assert_eq!(installer.hook_state(), HookState::UpdateAvailable);
+    assert_eq!(installer.dict_state(), DictState::NotSelected);
+    assert!(installer.dict_update().is_none());
+  }
+
+  #[test]
+  fn states() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut installer = installer(dir.path());
+    assert_eq!(installer.select_language("RU"), Some("Russian".to_string()));
+    assert_eq!(installer.dict_state(), DictState::UpdateAvailable);
+    installer.apply(&Event::DictUpdated(2));
+    assert_eq!(installer.dict_state(), DictState::UpToDate);
+
+    installer.set_binary(DfBinary {
+      checksum: 7,
+      ..installer.bin.clone()
+    });
+    assert_eq!(installer.hook_state(), HookState::Unsupported);
+    assert!(installer.hook_update().is_none());
+
+    installer.hook_metadata = HookMetadata::default();
+    assert_eq!(installer.hook_state(), HookState::NotLoaded);
+  }
+
+  #[test]
+  fn for_binary() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut installer = installer(dir.path());
+    installer.keep_local_changes = true;
+    let other = tempfile::tempdir().unwrap();
+    let other_installer = self::installer(other.path());
+    Receipt::update(other.path(), |receipt| {
+      receipt.dictionary = Some(Record {
+        language: Some("Russian".to_string()),
+        ..Default::default()
+      })
+    })
+    .unwrap();
+
+    let sibling = installer.for_binary(other_installer.bin.clone());
+    assert_eq!(sibling.bin.dir, other.path());
+    assert_eq!(sibling.language, "Russian");
+    assert_eq!(sibling.hook_state(), HookState::UpdateAvailable);
+    assert!(sibling.keep_local_changes);
+    assert_eq!(installer.language, NO_LANGUAGE);
+  }
+
+  #[test]
+  fn remove_hook_data() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut installer = installer(dir.path());
+    std::fs::create_dir_all(dir.path().join(PATH_DATA)).unwrap();
+    std::fs::write(dir.path().join(PATH_DICT), "a,b").unwrap();
+    std::fs::write(&installer.bin.lib_path, "lib").unwrap();
+    installer.refresh();
+    assert_ne!(installer.dict_checksum, NO_DICT_CHECKSUM);
+
+    let event = installer.remove_hook_data();
+    installer.apply(&event);
+    assert_eq!(installer.dict_checksum, NO_DICT_CHECKSUM);
+    assert!(!dir.path().join(PATH_DATA).exists());
+    assert!(!installer.bin.lib_path.exists());
+    assert_eq!(std::fs::read(dir.path().join(PATH_FONT)).unwrap(), ORIGINAL_FONT);
+  }
+
+  #[test]
+  fn remove_by_receipt() {
+    let dir = tempfile::tempdir().unwrap();
+    let installer = installer(dir.path());
+    let user_file = dir.path().join(PATH_DATA).join("notes.txt");
+    std::fs::write(&installer.bin.dfhooks_path, "third-party dfhooks").unwrap();
+    Backup::new(dir.path())
+      .snapshot(std::slice::from_ref(&installer.bin.dfhooks_path))
+      .unwrap();
+
+    let mut files = vec![];
+    for path in ["libdfhooks_dfint.so", "libdfhooks.so", PATH_DICT, PATH_FONT] {
+      let target = dir.path().join(path);
+      std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+      std::fs::write(&target, "installed").unwrap();
+      files.push(FileRecord::new(dir.path(), Path::new(path), "/url").unwrap());
+    }
+    std::fs::write(&user_file, "kept").unwrap();
+    Receipt::update(dir.path(), |receipt| {
+      receipt.hook = Some(Record {
+        files,
+        ..Default::default()
+      })
+    })
+    .unwrap();
+
+    installer.remove_hook_data();
+    assert!(!installer.bin.lib_path.exists());
+    assert!(!dir.path().join(PATH_DICT).exists());
+    assert!(Receipt::load(dir.path()).is_none());
+    assert_eq!(
+      std::fs::read_to_string(&installer.bin.dfhooks_path).unwrap(),
+      "third-party dfhooks"
+    );
+    assert_eq!(std::fs::read(dir.path().join(PATH_FONT)).unwrap(), ORIGINAL_FONT);
+    assert_eq!(std::fs::read_to_string(&user_file).unwrap(), "kept");
+  }
+
+  #[test]
+  fn old_data() {
+    let dir = tempfile::tempdir().unwrap();
+    let installer = installer(dir.path());
+    assert!(!installer.old_data_exists());
+    std::fs::create_dir_all(dir.path().join("dfint_data")).unwrap();
+    assert!(installer.old_data_exists());
+    installer.remove_old_data();
+    assert!(!installer.old_data_exists());
+  }
+}
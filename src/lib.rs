@@ -0,0 +1,27 @@
+#[macro_use]
+extern crate serde_derive;
+
+pub mod agent;
+pub mod backup;
+pub mod bundle;
+pub mod cache;
+pub mod constants;
+pub mod df_binary;
+pub mod dict_metadata;
+pub mod discovery;
+pub mod fetch;
+pub mod hook_metadata;
+pub mod http_cache;
+pub mod identify;
+pub mod installer;
+pub mod mirrors;
+pub mod persistent;
+pub mod progress;
+pub mod receipt;
+pub mod settings;
+pub mod signature;
+#[cfg(test)]
+mod test_server;
+pub mod transaction;
+pub mod utils;
+pub mod verify;
@@ -1,18 +1,27 @@
 use anyhow::Result;
-use eframe::egui;
-use std::ffi::OsStr;
-use std::path::PathBuf;
-
-use crate::{
-  app::{App, State},
+use dfint_installer::{
+  bundle::{Bundle, Contents, is_bundle},
+  cache::cache,
   constants::*,
-  df_binary::DfBinary,
+  df_binary::{Compat, DfBinary, DfBinaryError},
   dict_metadata::DictMetadata,
-  fetch::batch_download_to_file,
+  discovery::{DEFAULT_SCAN_DEPTH, discover},
   hook_metadata::HookMetadata,
-  localization::{LOCALE, t},
+  installer::{DictState, Event, HookState, Installer, NO_LANGUAGE},
+  mirrors::is_transient,
   persistent::Store,
+  progress::{Progress, Reporter, Transfers},
+  settings::Settings,
   utils::*,
+  verify::{FileReport, FileState},
+};
+use eframe::egui::{self, Color32};
+use std::path::{Path, PathBuf};
+
+use crate::{
+  app::{App, State},
+  localization::{LOCALE, t},
+  ui_constants::*,
 };
 
 macro_rules! error {
@@ -28,17 +37,128 @@ macro_rules! error {
 pub enum Message {
   HookMetadataLoaded(Result<HookMetadata>),
   DictMetadataLoaded(Result<DictMetadata>),
-  HookUpdated(Result<()>),
-  DictUpdated(Result<()>),
-  StoreLoaded((PathBuf, String, HookMetadata, DictMetadata)),
+  HookUpdated(Result<Event>),
+  DictUpdated(Result<Event>),
+  StoreLoaded(Box<(PathBuf, String, HookMetadata, DictMetadata)>),
+  Progress(Progress),
+  BundleLoaded(Result<Box<(Bundle, HookMetadata, DictMetadata)>>),
+  BundleExported(Result<Contents>),
+  InstallationsUpdated(Vec<(PathBuf, Result<Vec<Event>>)>),
+  InstallationsDiscovered(Vec<PathBuf>),
   DfRunning(bool),
 }
 
+/// Date and time of an RFC 3339 timestamp in the local time zone, as is if it doesn't parse.
+pub fn date_text(timestamp: &str) -> String {
+  match chrono::DateTime::parse_from_rfc3339(timestamp) {
+    Ok(date) => date
+      .with_timezone(&chrono::Local)
+      .format("%Y-%m-%d %H:%M")
+      .to_string(),
+    Err(_) => timestamp.to_string(),
+  }
+}
+
+/// OS of the build, and the layer it runs in if it's a Windows build on another OS.
+pub fn os_text(bin: &DfBinary) -> String {
+  match bin.compat {
+    Some(Compat::Proton) => t!("Windows build under Proton"),
+    Some(Compat::Wine) => t!("Windows build under Wine"),
+    None => bin.os.to_string(),
+  }
+}
+
+/// Why a file can't be used as the DF executable, and what to do about it.
+pub fn binary_error_text(err: &DfBinaryError) -> String {
+  match err {
+    DfBinaryError::NotFound => t!("The file doesn't exist, pick the executable in the DF folder"),
+    DfBinaryError::NotDfExecutable => {
+      t!("Not a Dwarf Fortress executable, pick 'Dwarf Fortress.exe', 'dwarfort' or 'Dwarf Fortress.app'")
+    }
+    DfBinaryError::PermissionDenied => t!("No permission to read the file, check its permissions"),
+    DfBinaryError::Unreadable(err) => format!(
+      "{} ({err})",
+      t!("Unable to read the file, check that the drive is available")
+    ),
+    DfBinaryError::UnsupportedPe32 => t!("32-bit Windows builds are not supported, install the 64-bit DF"),
+    DfBinaryError::CorruptHeader(detail) => format!(
+      "{} ({detail})",
+      t!("The executable is damaged, verify the game files in Steam or reinstall DF")
+    ),
+  }
+}
+
+/// State of the hook with its color.
+pub fn hook_status(installer: &Installer) -> (String, Color32) {
+  match installer.hook_state() {
+    HookState::NotLoaded => (format!("✖ {}", t!("hook data was not loaded")), COLOR_ERROR),
+    HookState::Unsupported => (format!("✖ {}", t!("this DF version is not supported")), COLOR_ERROR),
+    HookState::UpToDate => (format!("✅ {}", t!("up-to-date")), COLOR_UP_TO_DATE),
+    HookState::UpdateAvailable if !installer.hook_changes().is_empty() => {
+      (format!("⚠ {}", t!("files modified locally")), COLOR_UPDATE_AVAILABLE)
+    }
+    HookState::UpdateAvailable => (format!("⚠ {}", t!("update available")), COLOR_UPDATE_AVAILABLE),
+  }
+}
+
+/// State of the dictionary with its color.
+pub fn dict_status(installer: &Installer) -> (String, Color32) {
+  match installer.dict_state() {
+    DictState::NotLoaded => (format!("✖ {}", t!("dictionary data was not loaded")), COLOR_ERROR),
+    DictState::UpToDate => (format!("✅ {}", t!("up-to-date")), COLOR_UP_TO_DATE),
+    DictState::UpdateAvailable if !installer.dict_changes().is_empty() => {
+      (format!("⚠ {}", t!("files modified locally")), COLOR_UPDATE_AVAILABLE)
+    }
+    DictState::UpdateAvailable => (format!("⚠ {}", t!("update available")), COLOR_UPDATE_AVAILABLE),
+    DictState::NotSelected => (format!("⚠ {}", t!("choose language")), COLOR_CHOOSE_LANGUAGE),
+  }
+}
+
+/// One line per file, for tooltips and dialogs.
+pub fn changes_text(changes: &[&FileReport]) -> String {
+  changes
+    .iter()
+    .map(|file| {
+      let state = match file.state {
+        FileState::Missing => t!("missing"),
+        _ => t!("modified"),
+      };
+      format!("{}: {}", file.path.display(), state)
+    })
+    .collect::<Vec<String>>()
+    .join("\n")
+}
+
+/// Downloaded and total size with the time left, e.g. "1.2 / 3.4 MB, 5 s".
+pub fn transfers_text(transfers: &Transfers) -> String {
+  let megabytes = |bytes: u64| bytes as f64 / 1024. / 1024.;
+  let mut text = format!(
+    "{:.1} / {:.1} MB",
+    megabytes(transfers.downloaded()),
+    megabytes(transfers.total().unwrap_or_default())
+  );
+  if let Some(eta) = transfers.eta() {
+    text.push_str(&format!(", {} {}", eta.as_secs() + 1, t!("s left")));
+  }
+  text
+}
+
+fn system_language_code() -> String {
+  sys_locale::get_locale()
+    .unwrap_or("en-US".to_string())
+    .split('-')
+    .collect::<Vec<&str>>()[0]
+    .to_owned()
+}
+
 impl App {
   pub fn file_dialog(&self, dir: Option<PathBuf>) -> Option<egui_file::FileDialog> {
     let mut dialog = egui_file::FileDialog::open_file(self.opened_file.clone())
+      // renamed executables are accepted too, they are told by their format
       .show_files_filter(Box::new(|path| {
-        path.file_name() == Some(OsStr::new("Dwarf Fortress.exe")) || path.file_name() == Some(OsStr::new("dwarfort"))
+        path
+          .extension()
+          .is_none_or(|extension| extension.eq_ignore_ascii_case("exe"))
       }))
       .resizable(false)
       .show_rename(false)
@@ -50,75 +170,497 @@ impl App {
     Some(dialog)
   }
 
+  pub fn bundle_dialog(&self) -> Option<egui_file::FileDialog> {
+    let mut dialog = egui_file::FileDialog::open_file(None)
+      .show_files_filter(Box::new(is_bundle))
+      .resizable(false)
+      .show_rename(false)
+      .show_new_folder(false)
+      .title(&t!("Open offline bundle"))
+      .default_size([720., 381.]);
+    dialog.open();
+    Some(dialog)
+  }
+
+  pub fn opened_bundle_dialog(&mut self, ctx: &egui::Context) {
+    if let Some(dialog) = &mut self.open_bundle_dialog
+      && dialog.show(ctx).selected()
+      && let Some(file) = dialog.path()
+    {
+      let file = file.to_path_buf();
+      let checksum = self.installer.bin.checksum;
+      let language = self.installer.language.clone();
+      self.loading += 1;
+      self.pool.execute(
+        async move { Ok(Box::new(Bundle::load(file, checksum, Some(language)).await?)) },
+        Message::BundleLoaded,
+      );
+      self.open_bundle_dialog = None;
+    }
+  }
+
+  pub fn export_dialog(&self) -> Option<egui_file::FileDialog> {
+    let mut dialog = egui_file::FileDialog::save_file(None)
+      .default_filename(format!("dfint-bundle-{}.zip", self.installer.language.to_lowercase()))
+      .resizable(false)
+      .show_rename(false)
+      .title(&t!("Save offline bundle"))
+      .default_size([720., 381.]);
+    dialog.open();
+    Some(dialog)
+  }
+
+  pub fn opened_export_dialog(&mut self, ctx: &egui::Context) {
+    if let Some(dialog) = &mut self.export_bundle_dialog
+      && dialog.show(ctx).selected()
+      && let Some(file) = dialog.path()
+    {
+      let file = file.to_path_buf();
+      self.export_bundle_dialog = None;
+      self.track_progress();
+      if let Some(job) = self.installer.export_bundle(file) {
+        self.loading += 1;
+        self.pool.execute(job, Message::BundleExported);
+      }
+    }
+  }
+
   pub fn opened_file_dialog(&mut self, ctx: &egui::Context) {
     if let Some(dialog) = &mut self.open_file_dialog {
-      if dialog.state() == egui_file::State::Closed && !self.bin.valid {
+      if dialog.state() == egui_file::State::Closed && !self.installer.bin.valid {
         ctx.send_viewport_cmd(egui::ViewportCommand::Close)
       }
       if dialog.show(ctx).selected()
         && let Some(file) = dialog.path()
       {
-        self.bin = DfBinary::new(file.to_path_buf());
-        self.hook_checksum = self.local_hook_checksum().unwrap_or(0);
-        self.dict_checksum = self.local_dict_checksum().unwrap_or(0);
-        self.hook_metadata.pick_df_checksum(self.bin.checksum);
-        self.delete_hook_show = self.delete_old_data_check();
+        match DfBinary::new(file.to_path_buf()) {
+          Ok(bin) => {
+            self.installer.set_binary(bin);
+            self.delete_hook_show = self.installer.old_data_exists();
+            self.register_installation();
+          }
+          Err(err) => {
+            self.toast.error(binary_error_text(&err));
+            let dir = file.parent().map(Path::to_path_buf);
+            self.open_file_dialog = self.file_dialog(dir);
+          }
+        }
       }
     }
   }
 
-  pub fn on_close(&mut self) {
-    if self.bin.valid {
-      let _ = Store {
-        bin: self.bin.to_string(),
-        hook_manifest: self.hook_metadata.manifest.clone(),
-        vec_hook_manifests: self.hook_metadata.vec_manifests.clone(),
-        dict_manifest: self.dict_metadata.manifest.clone(),
-        vec_dict_manifests: self.dict_metadata.vec_manifests.clone(),
-        selected_language: self.selected_language.clone(),
+  /// Add the current DF installation to the list of installations.
+  pub fn register_installation(&mut self) {
+    if self.installer.bin.valid
+      && self.settings.register(&self.installer.bin.path)
+      && let Err(err) = self.settings.save()
+    {
+      error!(self, t!("Unable to save settings"), err.to_string());
+    }
+    if self.installations_show {
+      self.refresh_installations();
+    }
+  }
+
+  /// Read the state of all registered installations, the ones that can't be read are kept with the reason.
+  pub fn refresh_installations(&mut self) {
+    self.installations.clear();
+    self.invalid_installations.clear();
+    for path in &self.settings.installations {
+      match DfBinary::new(path.clone()) {
+        Ok(bin) => self.installations.push(self.installer.for_binary(bin)),
+        Err(err) => self
+          .invalid_installations
+          .push((path.clone(), binary_error_text(&err))),
       }
-      .save();
+    }
+    let known: Vec<&Path> = self
+      .installations
+      .iter()
+      .map(|item| item.bin.path.as_path())
+      .collect();
+    self
+      .selected_installations
+      .retain(|path| known.contains(&path.as_path()));
+  }
+
+  /// Update the given installations in one job.
+  pub fn update_installations(&mut self, paths: Vec<PathBuf>) {
+    self.track_progress();
+    let installers: Vec<Installer> = paths
+      .into_iter()
+      .filter_map(|path| DfBinary::new(path).ok())
+      .map(|bin| self.installer.for_binary(bin))
+      .filter(|installer| installer.update_available())
+      .collect();
+    if installers.is_empty() {
+      return;
+    }
+    self.loading += 1;
+    self
+      .pool
+      .execute(Installer::update_each(installers), Message::InstallationsUpdated);
+  }
+
+  /// Search this machine for installations in a job, new ones are registered.
+  pub fn scan_installations(&mut self) {
+    self.loading += 1;
+    let roots = self.settings.scan_roots.clone();
+    self.pool.execute(
+      async move { discover(&roots).into_iter().map(|bin| bin.path).collect() },
+      Message::InstallationsDiscovered,
+    );
+  }
+
+  pub fn installations_window(&mut self, ctx: &egui::Context) {
+    enum Action {
+      Switch(PathBuf),
+      Remove(PathBuf),
+      Update(Vec<PathBuf>),
+      Scan,
+    }
+
+    let mut open = self.installations_show;
+    let mut action = None;
+    egui::Window::new(t!("Installations"))
+      .open(&mut open)
+      .collapsible(false)
+      .resizable(false)
+      .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+      .show(ctx, |ui| {
+        egui::Grid::new("installations grid")
+          .num_columns(6)
+          .spacing([10., 5.])
+          .striped(true)
+          .show(ui, |ui| {
+            for installation in &self.installations {
+              let path = installation.bin.path.clone();
+              let mut selected = self.selected_installations.contains(&path);
+              let current = path == self.installer.bin.path;
+              let name = installation.bin.dir.display().to_string();
+              let checkbox = ui.checkbox(&mut selected, if current { format!("▶ {name}") } else { name });
+              if checkbox.changed() {
+                match selected {
+                  true => self.selected_installations.push(path.clone()),
+                  false => self.selected_installations.retain(|item| item != &path),
+                }
+              }
+              ui.label(format!(
+                "{} | {} | {}",
+                installation.bin.version,
+                os_text(&installation.bin),
+                match installation.bin.steam {
+                  true => "steam",
+                  false => "non-steam",
+                }
+              ));
+              let (text, color) = hook_status(installation);
+              ui.colored_label(color, text);
+              let (text, color) = dict_status(installation);
+              ui.colored_label(color, format!("{}: {text}", installation.language));
+              if ui
+                .add_enabled(!current && self.loading == 0, egui::Button::new("➡").small())
+                .on_hover_text(t!("Switch to this installation"))
+                .clicked()
+              {
+                action = Some(Action::Switch(path.clone()));
+              }
+              if ui
+                .add_enabled(!current, egui::Button::new("🗑").small())
+                .on_hover_text(t!("Remove from the list"))
+                .clicked()
+              {
+                action = Some(Action::Remove(path));
+              }
+              ui.end_row();
+            }
+            for (path, error) in &self.invalid_installations {
+              ui.label(path.display().to_string());
+              ui.colored_label(COLOR_ERROR, format!("✖ {error}"));
+              ui.label("");
+              ui.label("");
+              ui.label("");
+              if ui
+                .add(egui::Button::new("🗑").small())
+                .on_hover_text(t!("Remove from the list"))
+                .clicked()
+              {
+                action = Some(Action::Remove(path.clone()));
+              }
+              ui.end_row();
+            }
+          });
+
+        ui.add_space(5.);
+        ui.horizontal(|ui| {
+          if ui.button(format!("➕ {}", t!("Add"))).clicked() {
+            self.open_file_dialog = self.file_dialog(None);
+          }
+          if ui
+            .add_enabled(self.loading == 0, egui::Button::new(format!("🔍 {}", t!("Scan"))))
+            .on_hover_text(t!("Search Steam, itch.io and the scan folders for installations"))
+            .clicked()
+          {
+            action = Some(Action::Scan);
+          }
+          let pending: Vec<PathBuf> = self
+            .installations
+            .iter()
+            .filter(|installation| installation.update_available())
+            .map(|installation| installation.bin.path.clone())
+            .collect();
+          let selected: Vec<PathBuf> = pending
+            .iter()
+            .filter(|path| self.selected_installations.contains(path))
+            .cloned()
+            .collect();
+          if self.loading > 0 {
+            ui.spinner();
+          } else {
+            if ui
+              .add_enabled(!selected.is_empty(), egui::Button::new(t!("Update selected")))
+              .clicked()
+            {
+              action = Some(Action::Update(selected));
+            }
+            if ui
+              .add_enabled(!pending.is_empty(), egui::Button::new(t!("Update all")))
+              .clicked()
+            {
+              action = Some(Action::Update(pending));
+            }
+          }
+        });
+      });
+    self.installations_show = open;
+
+    match action {
+      Some(Action::Switch(path)) => match DfBinary::new(path) {
+        Ok(bin) => {
+          self.installer = self.installer.for_binary(bin);
+          self.delete_hook_show = self.installer.old_data_exists();
+        }
+        Err(err) => {
+          self.toast.error(binary_error_text(&err));
+        }
+      },
+      Some(Action::Remove(path)) => {
+        self.settings.unregister(&path);
+        if let Err(err) = self.settings.save() {
+          error!(self, t!("Unable to save settings"), err.to_string());
+        }
+        self.refresh_installations();
+      }
+      Some(Action::Update(paths)) => self.update_installations(paths),
+      Some(Action::Scan) => self.scan_installations(),
+      None => (),
+    }
+  }
+
+  /// Every DF build the hook supports, with the opened one and the nearest supported one marked.
+  pub fn compat_window(&mut self, ctx: &egui::Context) {
+    let metadata = &self.installer.hook_metadata;
+    let bin = &self.installer.bin;
+    let supported = metadata
+      .vec_manifests
+      .iter()
+      .any(|item| item.df == bin.checksum);
+    let nearest = match supported {
+      true => None,
+      false => metadata.nearest(bin),
+    };
+    let mut manifests: Vec<_> = metadata.vec_manifests.iter().collect();
+    manifests.sort_by_key(|item| std::cmp::Reverse((DfBinary::version_key(&item.version), item.os)));
+
+    egui::Window::new(t!("Supported DF builds"))
+      .open(&mut self.compat_show)
+      .collapsible(false)
+      .resizable(false)
+      .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+      .show(ctx, |ui| {
+        if !supported {
+          let text = match nearest {
+            Some(item) if DfBinary::version_key(&item.version) > DfBinary::version_key(&bin.version) => {
+              format!(
+                "{} {}",
+                t!("This DF version is not supported, update DF to"),
+                item.version
+              )
+            }
+            Some(item) => format!(
+              "{} {}",
+              t!("This DF version is not supported, roll DF back to"),
+              item.version
+            ),
+            None => t!("This DF version is not supported"),
+          };
+          ui.colored_label(COLOR_ERROR, text);
+          ui.add_space(5.);
+        }
+        egui::ScrollArea::vertical()
+          .max_height(300.)
+          .show(ui, |ui| {
+            egui::Grid::new("compat grid")
+              .num_columns(5)
+              .spacing([10., 5.])
+              .striped(true)
+              .show(ui, |ui| {
+                ui.strong(t!("Version"));
+                ui.strong("OS");
+                ui.strong(t!("Store"));
+                ui.strong(t!("Hook"));
+                ui.strong(t!("Checksum"));
+                ui.end_row();
+                for item in manifests {
+                  let (marker, color) = if item.df == bin.checksum {
+                    ("▶ ", COLOR_UP_TO_DATE)
+                  } else if nearest.is_some_and(|nearest| nearest.df == item.df) {
+                    ("➡ ", COLOR_UPDATE_AVAILABLE)
+                  } else {
+                    ("", ui.visuals().text_color())
+                  };
+                  let unknown = |text: &str| match text.is_empty() {
+                    true => "?".to_string(),
+                    false => text.to_string(),
+                  };
+                  ui.colored_label(color, format!("{marker}{}", unknown(&item.version)));
+                  ui.colored_label(color, item.os.map_or("?".to_string(), |os| os.to_string()));
+                  ui.colored_label(color, unknown(&item.store));
+                  ui.colored_label(color, item.checksum.to_string());
+                  ui.colored_label(color, format!("{:x}", item.df));
+                  ui.end_row();
+                }
+              });
+          });
+      });
+  }
+
+  pub fn on_close(&mut self) {
+    if self.installer.bin.valid {
+      let _ = Store::from(&self.installer).save();
     }
   }
 
   pub fn update_state(&mut self) {
+    // jobs can't be started while the messages are borrowed
+    let mut start_update = false;
+    let mut register_installation = false;
     for msg in self.pool.poll() {
       match msg {
-        Message::HookMetadataLoaded(result) => match result {
-          Ok(metadata) => {
-            self.hook_metadata = metadata;
-            if self.hook_metadata.manifest.checksum == 0 && self.bin.valid {
-              error!(self, t!("This DF version is not supported"));
+        // metadata of an opened offline bundle takes precedence
+        Message::HookMetadataLoaded(_) | Message::DictMetadataLoaded(_) if self.installer.bundle.is_some() => {
+          self.loading -= 1;
+        }
+        Message::HookMetadataLoaded(result) => {
+          self.loading -= 1;
+          match result {
+            Ok(metadata) => {
+              self.installer.set_hook_metadata(metadata);
+              if self.installer.hook_state() == HookState::Unsupported && self.installer.bin.valid {
+                error!(self, t!("This DF version is not supported"));
+              }
+            }
+            // offline, the stored manifests are used
+            Err(err) if is_transient(&err) && !self.installer.hook_metadata.vec_manifests.is_empty() => (),
+            Err(err) => {
+              error!(self, t!("Unable to fetch hook metadata..."), err.to_string());
             }
           }
-          Err(err) => {
-            error!(self, t!("Unable to fetch hook metadata..."), err.to_string());
+        }
+        Message::DictMetadataLoaded(result) => {
+          self.loading -= 1;
+          match result {
+            Ok(metadata) => {
+              self.installer.set_dict_metadata(metadata);
+              if self.installer.language == NO_LANGUAGE {
+                self.installer.select_language(&system_language_code());
+              }
+            }
+            Err(err) if is_transient(&err) && !self.installer.dict_metadata.vec_manifests.is_empty() => (),
+            Err(err) => {
+              error!(self, t!("Unable to fetch hook metadata..."), err.to_string());
+            }
           }
-        },
-        Message::DictMetadataLoaded(result) => match result {
-          Ok(metadata) => {
-            self.dict_metadata = metadata;
-            if self.selected_language == "None"
-              && let Some(language) = self.dict_metadata.pick_language_by_code(Some(
-                sys_locale::get_locale()
-                  .unwrap_or("en-US".to_string())
-                  .split('-')
-                  .collect::<Vec<&str>>()[0]
-                  .to_owned(),
-              ))
-            {
-              self.selected_language = language
+        }
+        Message::Progress(progress) => self.transfers.update(progress),
+        Message::BundleExported(result) => {
+          self.loading -= 1;
+          match result {
+            Ok(_) => {
+              self.toast.success(t!("Offline bundle saved"));
+            }
+            Err(err) => {
+              error!(self, t!("Unable to save offline bundle"), format!("{err:#}"));
             }
           }
-          Err(err) => {
-            error!(self, t!("Unable to fetch hook metadata..."), err.to_string());
+        }
+        Message::InstallationsUpdated(results) => {
+          self.loading -= 1;
+          let mut updated = 0;
+          for (path, result) in results {
+            match result {
+              Ok(_) => updated += 1,
+              Err(err) => {
+                error!(
+                  self,
+                  format!("{} {}", t!("Unable to update"), path.display()),
+                  format!("{err:#}")
+                );
+              }
+            }
           }
-        },
+          if updated > 0 {
+            self
+              .toast
+              .success(format!("{}: {updated}", t!("Installations updated")));
+          }
+          self.installer.refresh();
+          register_installation = true;
+        }
+        Message::InstallationsDiscovered(paths) => {
+          self.loading -= 1;
+          let found = paths
+            .iter()
+            .filter(|path| self.settings.register(path))
+            .count();
+          if found > 0 {
+            if let Err(err) = self.settings.save() {
+              error!(self, t!("Unable to save settings"), err.to_string());
+            }
+            self
+              .toast
+              .success(format!("{}: {found}", t!("New installations found")));
+          } else {
+            self.toast.info(t!("No new installations found"));
+          }
+          register_installation = true;
+        }
+        Message::BundleLoaded(result) => {
+          self.loading -= 1;
+          match result {
+            Ok(loaded) => {
+              let (bundle, hook_metadata, dict_metadata) = *loaded;
+              self
+                .installer
+                .set_bundle(bundle, hook_metadata, dict_metadata);
+              if self.installer.hook_state() == HookState::Unsupported {
+                error!(self, t!("This DF version is not supported"));
+              } else if self.installer.local_changes().is_empty() {
+                start_update = true;
+              } else {
+                self.keep_local_show = true;
+              }
+            }
+            Err(err) => {
+              error!(self, t!("Unable to open offline bundle"), format!("{err:#}"));
+            }
+          }
+        }
         Message::HookUpdated(result) => {
           match result {
-            Ok(_) => {
+            Ok(event) => {
               self.toast.success(t!("Hook updated"));
-              self.hook_checksum = self.local_hook_checksum().unwrap_or(0)
+              self.installer.apply(&event);
             }
             Err(err) => {
               error!(self, t!("Unable to update hook..."), err.to_string());
@@ -128,9 +670,9 @@ impl App {
         }
         Message::DictUpdated(result) => {
           match result {
-            Ok(_) => {
+            Ok(event) => {
               self.toast.success(t!("Dictionary updated"));
-              self.dict_checksum = self.local_dict_checksum().unwrap_or(0)
+              self.installer.apply(&event);
             }
             Err(err) => {
               let is_permission_denied = err.chain().any(|cause| {
@@ -153,37 +695,34 @@ impl App {
           };
           self.loading -= 1;
         }
-        Message::StoreLoaded((bin, selected_language, hook_metadata, dict_metadata)) => {
-          self.bin = DfBinary::new(bin);
-          self.selected_language = selected_language;
-          self.hook_metadata = hook_metadata;
-          self.dict_metadata = dict_metadata;
+        Message::StoreLoaded(store) => {
+          let (bin, selected_language, hook_metadata, dict_metadata) = *store;
+          let bin = match DfBinary::new(bin) {
+            Ok(bin) => bin,
+            // nothing was found on the first start, the file dialog asks for it
+            Err(DfBinaryError::NotDfExecutable) => DfBinary::default(),
+            Err(err) => {
+              self.toast.error(binary_error_text(&err));
+              DfBinary::default()
+            }
+          };
+          self.installer = Installer::new(bin, selected_language, hook_metadata, dict_metadata);
 
-          self.hook_checksum = self.local_hook_checksum().unwrap_or(0);
+          self.loading += 2;
           self.pool.execute(
-            HookMetadata::from_url(URL_HOOK_MANIFEST, Some(self.bin.checksum)),
+            HookMetadata::from_url(URL_HOOK_MANIFEST, Some(self.installer.bin.checksum)),
             Message::HookMetadataLoaded,
           );
-
-          self.dict_checksum = self.local_dict_checksum().unwrap_or(0);
           self.pool.execute(
-            DictMetadata::from_url(URL_DICT_MANIFEST, Some(self.selected_language.clone())),
+            DictMetadata::from_url(URL_DICT_MANIFEST, Some(self.installer.language.clone())),
             Message::DictMetadataLoaded,
           );
 
-          if self.selected_language == "None"
-            && let Some(language) = self.dict_metadata.pick_language_by_code(Some(
-              sys_locale::get_locale()
-                .unwrap_or("en-US".to_string())
-                .split('-')
-                .collect::<Vec<&str>>()[0]
-                .to_owned(),
-            ))
-          {
-            self.selected_language = language;
+          if self.installer.language == NO_LANGUAGE {
+            self.installer.select_language(&system_language_code());
           }
-
-          self.delete_old_data_show = self.delete_old_data_check();
+          self.delete_old_data_show = self.installer.old_data_exists();
+          register_installation = true;
           self.state = State::Idle;
         }
         Message::DfRunning(result) => {
@@ -191,12 +730,20 @@ impl App {
         }
       }
     }
+    if start_update {
+      self.update_data();
+    }
+    if register_installation {
+      self.register_installation();
+    }
   }
 
   pub fn on_start(&mut self) {
     self.state = State::Loading;
     self.pool.execute(is_df_running(), Message::DfRunning);
-    self.pool.execute(Store::new(), Message::StoreLoaded);
+    self
+      .pool
+      .execute(async { Box::new(Store::new().await) }, Message::StoreLoaded);
   }
 
   pub fn guard(&mut self, ctx: &egui::Context, name: &str, text: &str) {
@@ -217,13 +764,6 @@ impl App {
     });
   }
 
-  pub fn delete_old_data_check(&self) -> bool {
-    if !self.bin.valid {
-      return false;
-    }
-    self.bin.dir.join("dfint_launcher.exe").exists() || self.bin.dir.join("dfint_data").exists()
-  }
-
   pub fn delete_old_hook_dialog(&mut self, ctx: &egui::Context) {
     self.dialog(
       ctx,
@@ -234,7 +774,8 @@ impl App {
       },
       |app| {
         app.delete_old_data_show = false;
-        app.remove_old_data();
+        let event = app.installer.remove_old_data();
+        app.installer.apply(&event);
         app.toast.success(t!("Old files successfully deleted"));
       },
     );
@@ -247,13 +788,12 @@ impl App {
       t!("Delete all localization files?"),
       |app| {
         app.delete_hook_show = false;
-        app.selected_language = "English".to_string();
+        app.installer.language = "English".to_string();
       },
       |app| {
         app.delete_hook_show = false;
-        app.remove_hook_data();
-        app.hook_checksum = app.local_hook_checksum().unwrap_or(0);
-        app.dict_checksum = app.local_dict_checksum().unwrap_or(0);
+        let event = app.installer.remove_hook_data();
+        app.installer.apply(&event);
         app
           .toast
           .success(t!("Localization files successfully deleted"));
@@ -261,6 +801,219 @@ impl App {
     );
   }
 
+  pub fn restore_original_dialog(&mut self, ctx: &egui::Context) {
+    self.dialog(
+      ctx,
+      "restore_original",
+      t!("Restore the game files as they were before the first installation? All localization files will be deleted."),
+      |app| {
+        app.restore_original_show = false;
+      },
+      |app| {
+        app.restore_original_show = false;
+        match app.installer.restore_original() {
+          Ok(event) => {
+            app.installer.apply(&event);
+            app.toast.success(t!("Original game files restored"));
+          }
+          Err(err) => {
+            error!(app, t!("Unable to restore original game files"), err.to_string());
+          }
+        }
+      },
+    );
+  }
+
+  pub fn keep_local_dialog(&mut self, ctx: &egui::Context) {
+    let text = format!(
+      "{}\n\n{}\n\n{}",
+      t!("These installed files were modified locally:"),
+      changes_text(&self.installer.local_changes()),
+      t!("Keep your changes? Otherwise the files will be overwritten.")
+    );
+    self.dialog(
+      ctx,
+      "keep_local",
+      text,
+      |app| {
+        app.keep_local_show = false;
+        app.installer.keep_local_changes = false;
+        app.update_data();
+      },
+      |app| {
+        app.keep_local_show = false;
+        app.installer.keep_local_changes = true;
+        app.update_data();
+      },
+    );
+  }
+
+  pub fn settings_window(&mut self, ctx: &egui::Context) {
+    enum Action {
+      Move(String, usize),
+      Remove(String),
+    }
+
+    let mut open = self.settings_show;
+    let mut changed = false;
+    egui::Window::new(t!("Settings"))
+      .open(&mut open)
+      .collapsible(false)
+      .resizable(false)
+      .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+      .show(ctx, |ui| {
+        ui.strong(t!("Mirrors"));
+        let mut action = None;
+        let count = self.settings.sources.len();
+        egui::Grid::new("mirrors grid")
+          .num_columns(4)
+          .spacing([5., 5.])
+          .striped(true)
+          .show(ui, |ui| {
+            for (index, source) in self.settings.sources.iter_mut().enumerate() {
+              let url = source.url.clone();
+              changed |= ui.checkbox(&mut source.enabled, &url).changed();
+              if ui
+                .add_enabled(index > 0, egui::Button::new("⬆").small())
+                .clicked()
+              {
+                action = Some(Action::Move(url.clone(), index - 1));
+              }
+              if ui
+                .add_enabled(index + 1 < count, egui::Button::new("⬇").small())
+                .clicked()
+              {
+                action = Some(Action::Move(url.clone(), index + 1));
+              }
+              if ui.small_button("🗑").clicked() {
+                action = Some(Action::Remove(url));
+              }
+              ui.end_row();
+            }
+          });
+        changed |= match action {
+          Some(Action::Move(url, position)) => self.settings.move_to(&url, position).is_ok(),
+          Some(Action::Remove(url)) => self.settings.remove(&url).is_ok(),
+          None => false,
+        };
+
+        ui.add_space(5.);
+        ui.horizontal(|ui| {
+          ui.add(egui::TextEdit::singleline(&mut self.new_mirror).hint_text("https://, http://, file://"));
+          if ui.small_button("➕").clicked() {
+            match self.settings.add(&self.new_mirror, None) {
+              Ok(()) => {
+                self.new_mirror.clear();
+                changed = true;
+              }
+              Err(err) => {
+                self
+                  .toast
+                  .error(format!("{}: {err}", t!("Unable to add mirror")));
+              }
+            }
+          }
+          if ui.small_button(t!("Reset")).clicked() {
+            self.settings.sources = Settings::default().sources;
+            changed = true;
+          }
+        });
+
+        ui.separator();
+        ui.strong(t!("Proxy"));
+        ui.horizontal(|ui| {
+          let field = ui.add(egui::TextEdit::singleline(&mut self.proxy).hint_text(t!("from environment")));
+          if field.lost_focus() {
+            match self.settings.set_proxy(&self.proxy) {
+              Ok(()) => changed = true,
+              Err(err) => {
+                self.toast.error(err.to_string());
+              }
+            }
+          }
+        });
+
+        ui.separator();
+        ui.strong(t!("Scan folders"));
+        let mut removed = None;
+        egui::Grid::new("scan roots grid")
+          .num_columns(3)
+          .spacing([5., 5.])
+          .striped(true)
+          .show(ui, |ui| {
+            for root in self.settings.scan_roots.iter_mut() {
+              ui.label(root.path.display().to_string());
+              changed |= ui
+                .add(egui::DragValue::new(&mut root.depth).range(0..=10))
+                .on_hover_text(t!("Folder levels searched below it"))
+                .changed();
+              if ui.small_button("🗑").clicked() {
+                removed = Some(root.path.clone());
+              }
+              ui.end_row();
+            }
+          });
+        if let Some(path) = removed {
+          self.settings.remove_scan_root(&path);
+          changed = true;
+        }
+        ui.horizontal(|ui| {
+          ui.add(egui::TextEdit::singleline(&mut self.new_scan_root).hint_text(t!("folder")));
+          if ui.small_button("➕").clicked() && !self.new_scan_root.trim().is_empty() {
+            self
+              .settings
+              .add_scan_root(Path::new(self.new_scan_root.trim()), DEFAULT_SCAN_DEPTH);
+            self.new_scan_root.clear();
+            changed = true;
+          }
+        });
+
+        ui.separator();
+        ui.strong(t!("Download cache"));
+        ui.horizontal(|ui| {
+          ui.label(t!("Size limit"));
+          changed |= ui
+            .add(
+              egui::DragValue::new(&mut self.settings.cache_limit)
+                .range(0..=16384)
+                .suffix(" MB"),
+            )
+            .on_hover_text(t!("0 disables the cache"))
+            .changed();
+          ui.label(format!("{} {:.1} MB", t!("in use:"), self.cache_size as f64 / 1048576.));
+          if ui.small_button(t!("Clear cache")).clicked() {
+            match cache().clear() {
+              Ok(_) => {
+                self.toast.success(t!("Cache cleared"));
+              }
+              Err(err) => {
+                error!(self, t!("Unable to clear cache"), err.to_string());
+              }
+            }
+            self.cache_size = cache().size();
+          }
+        });
+
+        ui.separator();
+        ui.strong(t!("Integrity"));
+        changed |= ui
+          .checkbox(
+            &mut self.settings.allow_unhashed,
+            t!("Install files without SHA-256 in the manifest"),
+          )
+          .on_hover_text(t!("They are only checked by the crc32, which a mirror can forge"))
+          .changed();
+      });
+    self.settings_show = open;
+
+    if changed {
+      self.settings.apply();
+      if let Err(err) = self.settings.save() {
+        error!(self, t!("Unable to save settings"), err.to_string());
+      }
+    }
+  }
+
   fn dialog(
     &mut self,
     ctx: &egui::Context,
@@ -289,62 +1042,29 @@ impl App {
     modal.open();
   }
 
-  pub fn update_data(&mut self) {
-    std::fs::create_dir_all(self.bin.dir.join(PATH_DATA)).expect("Unable to create directory");
+  /// Send the download progress of the next jobs to the progress bar.
+  fn track_progress(&mut self) {
+    let tx = self.pool.sender();
+    self.installer.progress = Reporter::new(move |progress| {
+      let _ = tx.send(Message::Progress(progress));
+    });
+    self.transfers.clear();
+  }
 
-    let hook_manifest = self.hook_metadata.manifest.clone();
-    if hook_manifest.df == self.bin.checksum && hook_manifest.checksum != self.hook_checksum {
+  pub fn update_data(&mut self) {
+    self.track_progress();
+    if self.settings.allow_unhashed && self.installer.unhashed() {
+      self
+        .toast
+        .warning(t!("Files without SHA-256 are installed, checked by the crc32 only"));
+    }
+    if let Some(job) = self.installer.hook_update() {
       self.loading += 1;
-      self.pool.execute(
-        batch_download_to_file(vec![
-          (hook_manifest.lib, self.bin.lib_path.clone()),
-          (hook_manifest.config, self.bin.dir.join(PATH_CONFIG)),
-          (hook_manifest.offsets, self.bin.dir.join(PATH_OFFSETS)),
-          (hook_manifest.dfhooks, self.bin.dfhooks_path.clone()),
-        ]),
-        Message::HookUpdated,
-      );
+      self.pool.execute(job, Message::HookUpdated);
     }
-
-    let dict_manifest = self.dict_metadata.manifest.clone();
-    if dict_manifest.checksum != self.dict_checksum && self.selected_language != "None" {
+    if let Some(job) = self.installer.dict_update() {
       self.loading += 1;
-      self.pool.execute(
-        batch_download_to_file(vec![
-          (dict_manifest.csv, self.bin.dir.join(PATH_DICT)),
-          (dict_manifest.font, self.bin.dir.join(PATH_FONT)),
-          (dict_manifest.encoding, self.bin.dir.join(PATH_ENCODING)),
-        ]),
-        Message::DictUpdated,
-      );
+      self.pool.execute(job, Message::DictUpdated);
     }
   }
-
-  pub fn local_hook_checksum(&self) -> Result<u32> {
-    checksum_for_files(vec![
-      self.bin.lib_path.clone(),
-      self.bin.dir.join(PATH_CONFIG),
-      self.bin.dir.join(PATH_OFFSETS),
-      self.bin.dfhooks_path.clone(),
-    ])
-  }
-
-  pub fn local_dict_checksum(&self) -> Result<u32> {
-    checksum_for_files(vec![
-      self.bin.dir.join(PATH_DICT),
-      self.bin.dir.join(PATH_FONT),
-      self.bin.dir.join(PATH_ENCODING),
-    ])
-  }
-
-  pub fn remove_old_data(&self) {
-    let _ = std::fs::remove_file(self.bin.dir.join("dfint_launcher.exe"));
-    let _ = std::fs::remove_dir_all(self.bin.dir.join("dfint_data"));
-  }
-
-  pub fn remove_hook_data(&self) {
-    let _ = std::fs::write(self.bin.dir.join(PATH_FONT), ORIGINAL_FONT);
-    let _ = std::fs::remove_file(self.bin.lib_path.clone());
-    let _ = std::fs::remove_dir_all(self.bin.dir.join("dfint-data"));
-  }
 }
@@ -1,27 +1,56 @@
-#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+#![cfg_attr(all(feature = "gui", not(debug_assertions)), windows_subsystem = "windows")]
 
 #[macro_use]
 extern crate serde_derive;
 
-use anyhow::Result;
-use constants::APP_ICON;
+use clap::Parser;
+use dfint_installer::settings::Settings;
+#[cfg(feature = "gui")]
 use eframe::egui;
+use std::process::ExitCode;
+#[cfg(feature = "gui")]
+use ui_constants::APP_ICON;
 // use eframe::egui::{Style, Visuals};
 
+#[cfg(feature = "gui")]
 mod app;
-mod constants;
-mod df_binary;
-mod dict_metadata;
-mod fetch;
-mod hook_metadata;
+mod cli;
+#[cfg(feature = "gui")]
 mod localization;
+#[cfg(feature = "gui")]
 mod logic;
-mod persistent;
+#[cfg(feature = "gui")]
 mod thread_pool;
-mod utils;
+#[cfg(feature = "gui")]
+mod ui_constants;
 
-fn main() -> Result<(), eframe::Error> {
+fn main() -> ExitCode {
   env_logger::init();
+  if std::env::args_os().len() > 1 {
+    cli::attach_console();
+  }
+  Settings::load().apply();
+  match cli::Cli::parse().command {
+    Some(command) => cli::run(command),
+    #[cfg(feature = "gui")]
+    None => match gui() {
+      Ok(_) => ExitCode::SUCCESS,
+      Err(err) => {
+        eprintln!("error: {err}");
+        ExitCode::FAILURE
+      }
+    },
+    // built without the window, only the commands are there
+    #[cfg(not(feature = "gui"))]
+    None => {
+      let _ = <cli::Cli as clap::CommandFactory>::command().print_help();
+      ExitCode::from(2)
+    }
+  }
+}
+
+#[cfg(feature = "gui")]
+fn gui() -> Result<(), eframe::Error> {
   let options = eframe::NativeOptions {
     viewport: egui::ViewportBuilder::default()
       .with_inner_size([720., 450.])
@@ -0,0 +1,320 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::{agent::agent, constants::BASE_URL};
+
+const ATTEMPTS: usize = 3;
+const BACKOFF: Duration = Duration::from_millis(500);
+const COOLDOWN: Duration = Duration::from_secs(30);
+/// Longest a failing mirror is skipped, the cooldown doubles with every failure up to this.
+const MAX_COOLDOWN: Duration = Duration::from_secs(600);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+/// Mirrors responding slower than this are tried after the fast ones.
+const SLOW: Duration = Duration::from_secs(1);
+
+static MIRRORS: LazyLock<RwLock<Arc<Mirrors>>> = LazyLock::new(|| RwLock::new(Arc::new(Mirrors::new(BASE_URL))));
+
+/// Mirrors of the localization data, used by all requests.
+pub fn mirrors() -> Arc<Mirrors> {
+  MIRRORS
+    .read()
+    .unwrap_or_else(|err| err.into_inner())
+    .clone()
+}
+
+/// Replace the mirrors used by all requests, the built-in ones are used if `urls` is empty.
+pub fn set_mirrors(urls: Vec<String>) {
+  let mirrors = match urls.is_empty() {
+    true => Mirrors::new(BASE_URL),
+    false => Mirrors::new(urls),
+  };
+  *MIRRORS.write().unwrap_or_else(|err| err.into_inner()) = Arc::new(mirrors);
+}
+
+/// Local directory of a `file://` mirror, `file:///C:/mirror` style URLs are accepted on Windows.
+pub fn file_url_path(url: &str) -> Option<PathBuf> {
+  let path = url.strip_prefix("file://")?;
+  let path = match path.strip_prefix('/') {
+    Some(rest) if rest.get(1..2) == Some(":") => rest,
+    _ => path,
+  };
+  Some(PathBuf::from(path))
+}
+
+#[derive(Debug, Default, Clone)]
+struct Health {
+  /// Failures in a row
+  failures: u32,
+  /// The mirror is tried only after the healthy ones until then
+  down_until: Option<Instant>,
+  /// Response time measured by the last probe
+  latency: Option<Duration>,
+}
+
+/// Base URLs serving the same files, with their health.
+///
+/// Requests go to the first healthy mirror in the list, mirrors that responded slowly to the probe
+/// are tried after the fast ones. Transient failures (connection errors,
+/// timeouts, 5xx) mark the mirror as down for a cooldown and are retried on the next mirror, then again
+/// after an exponential backoff. Permanent failures (4xx) are tried on the other mirrors once and not
+/// retried. Mirrors are probed before the first request and again when a cooldown is over, so a
+/// recovered primary mirror is picked up again.
+pub struct Mirrors {
+  urls: Vec<String>,
+  health: Mutex<Vec<Health>>,
+  attempts: usize,
+  backoff: Duration,
+  cooldown: Duration,
+  slow: Duration,
+}
+
+impl Mirrors {
+  pub fn new<S: Into<String>>(urls: impl IntoIterator<Item = S>) -> Self {
+    let urls: Vec<String> = urls.into_iter().map(Into::into).collect();
+    assert!(!urls.is_empty(), "at least one mirror is required");
+    Self {
+      health: Mutex::new(vec![Health::default(); urls.len()]),
+      urls,
+      attempts: ATTEMPTS,
+      backoff: BACKOFF,
+      cooldown: COOLDOWN,
+      slow: SLOW,
+    }
+  }
+
+  pub fn with_backoff(mut self, backoff: Duration) -> Self {
+    self.backoff = backoff;
+    self
+  }
+
+  pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+    self.cooldown = cooldown;
+    self
+  }
+
+  pub fn urls(&self) -> &[String] {
+    &self.urls
+  }
+
+  /// Mirror the next request goes to.
+  pub fn current(&self) -> &str {
+    &self.urls[self.order()[0]]
+  }
+
+  /// Run `request` with the full URL of `path`, moving through the mirrors on failures.
+  pub fn run<T>(&self, path: &str, mut request: impl FnMut(&str) -> Result<T>) -> Result<T> {
+    if self.needs_probe() {
+      self.probe();
+    }
+
+    let mut last = None;
+    for attempt in 0..self.attempts {
+      if attempt > 0 {
+        std::thread::sleep(self.backoff * 2u32.pow(attempt as u32 - 1));
+      }
+      let mut transient = false;
+      for index in self.order() {
+        match request(&format!("{}{}", self.urls[index], path)) {
+          Ok(value) => {
+            self.succeeded(index);
+            return Ok(value);
+          }
+          Err(err) => {
+            if is_transient(&err) {
+              self.failed(index);
+              transient = true;
+            }
+            last = Some(err);
+          }
+        }
+      }
+      if !transient {
+        break;
+      }
+    }
+    Err(last.expect("at least one mirror was tried"))
+  }
+
+  /// Before the first request and whenever a mirror may have recovered.
+  fn needs_probe(&self) -> bool {
+    let now = Instant::now();
+    self.urls.len() > 1
+      && self.health.lock().unwrap().iter().any(|health| {
+        health.latency.is_none() && health.failures == 0 || health.down_until.is_some_and(|until| until <= now)
+      })
+  }
+
+  /// Measure the response time of every mirror, unreachable mirrors are marked as down.
+  pub fn probe(&self) {
+    let agent = agent();
+    let latencies: Vec<Option<Duration>> = std::thread::scope(|scope| {
+      let probes: Vec<_> = self
+        .urls
+        .iter()
+        .map(|url| {
+          let agent = agent.clone();
+          scope.spawn(move || {
+            if let Some(path) = file_url_path(url) {
+              return path.is_dir().then_some(Duration::ZERO);
+            }
+            let start = Instant::now();
+            agent
+              .head(url)
+              .config()
+              .timeout_global(Some(PROBE_TIMEOUT))
+              .http_status_as_error(false)
+              .build()
+              .call()
+              .ok()
+              .filter(|response| !response.status().is_server_error())
+              .map(|_| start.elapsed())
+          })
+        })
+        .collect();
+      probes
+        .into_iter()
+        .map(|probe| probe.join().unwrap_or_default())
+        .collect()
+    });
+
+    for (index, latency) in latencies.into_iter().enumerate() {
+      match latency {
+        Some(latency) => {
+          self.health.lock().unwrap()[index] = Health {
+            latency: Some(latency),
+            ..Default::default()
+          }
+        }
+        None => self.failed(index),
+      }
+    }
+  }
+
+  /// Mirror indices in the order they should be tried: healthy first, then fast ones, then in the list order.
+  fn order(&self) -> Vec<usize> {
+    let now = Instant::now();
+    let health = self.health.lock().unwrap();
+    let mut order: Vec<usize> = (0..self.urls.len()).collect();
+    order.sort_by_key(|&index| {
+      let health = &health[index];
+      (
+        health.down_until.is_some_and(|until| until > now),
+        health.latency.is_some_and(|latency| latency > self.slow),
+        index,
+      )
+    });
+    order
+  }
+
+  fn succeeded(&self, index: usize) {
+    let mut health = self.health.lock().unwrap();
+    health[index].failures = 0;
+    health[index].down_until = None;
+  }
+
+  fn failed(&self, index: usize) {
+    let mut health = self.health.lock().unwrap();
+    let health = &mut health[index];
+    let cooldown = (self.cooldown * 2u32.pow(health.failures.min(10))).min(MAX_COOLDOWN);
+    health.failures += 1;
+    health.down_until = Some(Instant::now() + cooldown);
+  }
+}
+
+/// Whether the request may succeed when repeated: network errors, timeouts and server errors.
+/// Client errors like 404 and local IO errors are permanent.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+  err
+    .chain()
+    .find_map(|cause| cause.downcast_ref::<ureq::Error>())
+    .is_some_and(|err| match err {
+      ureq::Error::StatusCode(code) => *code >= 500 || matches!(code, 408 | 429),
+      ureq::Error::Io(_)
+      | ureq::Error::Timeout(_)
+      | ureq::Error::HostNotFound
+      | ureq::Error::ConnectionFailed
+      | ureq::Error::Protocol(_) => true,
+      _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_server::{Reply, TestServer, closed_url};
+
+  fn get(url: &str) -> Result<String> {
+    Ok(ureq::get(url).call()?.into_body().read_to_string()?)
+  }
+
+  #[test]
+  fn retries_transient_errors() {
+    let server = TestServer::start(|_, index| match index {
+      0 | 1 => Reply::status(503),
+      _ => Reply::ok("data"),
+    });
+    let mirrors = Mirrors::new([server.url.clone()]).with_backoff(Duration::from_millis(1));
+    assert_eq!(mirrors.run("/file", get).unwrap(), "data");
+    assert_eq!(server.requests().len(), 3);
+  }
+
+  #[test]
+  fn not_found_is_permanent() {
+    let server = TestServer::start(|_, _| Reply::status(404));
+    let mirrors = Mirrors::new([server.url.clone()]).with_backoff(Duration::from_millis(1));
+    let err = mirrors.run("/missing", get).unwrap_err();
+    assert!(!is_transient(&err));
+    assert_eq!(server.requests().len(), 1);
+    // the mirror itself is fine
+    assert_eq!(mirrors.current(), server.url);
+  }
+
+  #[test]
+  fn fails_over_and_returns_to_primary() {
+    let primary = TestServer::start(|_, index| match index {
+      0 => Reply::status(503),
+      _ => Reply::ok("primary"),
+    });
+    let secondary = TestServer::start(|_, _| Reply {
+      delay: Duration::from_millis(100),
+      ..Reply::ok("secondary")
+    });
+    let mirrors = Mirrors::new([primary.url.clone(), secondary.url.clone()]).with_cooldown(Duration::from_millis(50));
+    assert_eq!(mirrors.run("/file", get).unwrap(), "secondary");
+    assert_eq!(primary.requests().len(), 1);
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(mirrors.run("/file", get).unwrap(), "primary");
+  }
+
+  #[test]
+  fn file_urls() {
+    assert_eq!(file_url_path("file:///srv/mirror"), Some(PathBuf::from("/srv/mirror")));
+    assert_eq!(file_url_path("file:///C:/mirror"), Some(PathBuf::from("C:/mirror")));
+    assert_eq!(file_url_path("https://dfint.github.io"), None);
+  }
+
+  #[test]
+  fn unreachable_mirror() {
+    let server = TestServer::start(|_, _| Reply::ok("data"));
+    let mirrors = Mirrors::new([closed_url(), server.url.clone()]);
+    assert_eq!(mirrors.run("/file", get).unwrap(), "data");
+    assert_eq!(mirrors.current(), server.url);
+  }
+
+  #[test]
+  fn probe_skips_slow_mirror() {
+    let slow = TestServer::start(|_, _| Reply {
+      delay: Duration::from_millis(200),
+      ..Reply::ok("slow")
+    });
+    let fast = TestServer::start(|_, _| Reply::ok("fast"));
+    let mut mirrors = Mirrors::new([slow.url.clone(), fast.url.clone()]);
+    mirrors.slow = Duration::from_millis(100);
+    mirrors.probe();
+    assert_eq!(mirrors.current(), fast.url);
+    assert_eq!(mirrors.run("/file", get).unwrap(), "fast");
+  }
+}
@@ -5,6 +5,7 @@ use crate::{
   constants::PATH_CACHE_FILE,
   dict_metadata::{DictMetadata, Manifest as DictManifest},
   hook_metadata::{HookMetadata, Manifest as HookManifest},
+  installer::Installer,
   utils::scan_df,
 };
 
@@ -16,6 +17,9 @@ pub struct Store {
   pub dict_manifest: DictManifest,
   pub vec_dict_manifests: Vec<DictManifest>,
   pub selected_language: String,
+  /// When the manifests were stored, RFC 3339
+  #[serde(default)]
+  pub saved: Option<String>,
 }
 
 impl Store {
@@ -33,6 +37,11 @@ impl Store {
   pub async fn new() -> (PathBuf, String, HookMetadata, DictMetadata) {
     match Store::load() {
       Ok(store) => {
+        // stores written by older versions have no date, the file has one
+        let saved = store.saved.or_else(|| {
+          let modified = std::fs::metadata(PATH_CACHE_FILE).ok()?.modified().ok()?;
+          Some(chrono::DateTime::<chrono::Local>::from(modified).to_rfc3339())
+        });
         let mut bin = PathBuf::from(store.bin);
         if !bin.exists() {
           bin = scan_df().unwrap_or(std::env::current_dir().unwrap().to_path_buf());
@@ -43,10 +52,12 @@ impl Store {
           HookMetadata {
             manifest: store.hook_manifest,
             vec_manifests: store.vec_hook_manifests,
+            cached: saved.clone(),
           },
           DictMetadata {
             manifest: store.dict_manifest,
             vec_manifests: store.vec_dict_manifests,
+            cached: saved,
           },
         )
       }
@@ -59,3 +70,22 @@ impl Store {
     }
   }
 }
+
+impl From<&Installer> for Store {
+  fn from(installer: &Installer) -> Self {
+    Self {
+      bin: installer.bin.to_string(),
+      hook_manifest: installer.hook_metadata.manifest.clone(),
+      vec_hook_manifests: installer.hook_metadata.vec_manifests.clone(),
+      dict_manifest: installer.dict_metadata.manifest.clone(),
+      vec_dict_manifests: installer.dict_metadata.vec_manifests.clone(),
+      selected_language: installer.language.clone(),
+      saved: Some(
+        installer
+          .offline_since()
+          .map(str::to_string)
+          .unwrap_or_else(|| chrono::Local::now().to_rfc3339()),
+      ),
+    }
+  }
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Bytes of one file received so far.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+  /// Final location of the file
+  pub file: PathBuf,
+  pub downloaded: u64,
+  /// `None` if the server didn't send the size
+  pub total: Option<u64>,
+}
+
+/// Receiver of download progress, called from the download tasks. Silent by default.
+#[derive(Clone, Default)]
+pub struct Reporter(Option<Arc<dyn Fn(Progress) + Send + Sync>>);
+
+impl Reporter {
+  pub fn new(report: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+    Self(Some(Arc::new(report)))
+  }
+
+  pub fn report(&self, progress: Progress) {
+    if let Some(report) = &self.0 {
+      report(progress)
+    }
+  }
+}
+
+struct Transfer {
+  /// Bytes already on disk when the transfer was first seen, e.g. of a resumed download
+  resumed: u64,
+  progress: Progress,
+}
+
+/// Progress of all downloads of an update, collected from `Progress` reports.
+#[derive(Default)]
+pub struct Transfers {
+  files: BTreeMap<PathBuf, Transfer>,
+  started: Option<Instant>,
+}
+
+impl Transfers {
+  pub fn update(&mut self, progress: Progress) {
+    self.started.get_or_insert_with(Instant::now);
+    self
+      .files
+      .entry(progress.file.clone())
+      .and_modify(|transfer| transfer.progress = progress.clone())
+      .or_insert(Transfer {
+        resumed: progress.downloaded,
+        progress,
+      });
+  }
+
+  pub fn clear(&mut self) {
+    *self = Self::default();
+  }
+
+  pub fn downloaded(&self) -> u64 {
+    self
+      .files
+      .values()
+      .map(|transfer| transfer.progress.downloaded)
+      .sum()
+  }
+
+  /// Size of all files, `None` until every file reported its size.
+  pub fn total(&self) -> Option<u64> {
+    if self.files.is_empty() {
+      return None;
+    }
+    self
+      .files
+      .values()
+      .map(|transfer| transfer.progress.total)
+      .sum()
+  }
+
+  pub fn fraction(&self) -> Option<f32> {
+    let total = self.total()?;
+    if total == 0 {
+      return Some(1.);
+    }
+    Some(self.downloaded() as f32 / total as f32)
+  }
+
+  /// Estimated time left at the average speed since the first report.
+  pub fn eta(&self) -> Option<Duration> {
+    self.estimate(self.started?.elapsed())
+  }
+
+  fn estimate(&self, elapsed: Duration) -> Option<Duration> {
+    let remaining = self.total()?.saturating_sub(self.downloaded());
+    let received: u64 = self
+      .files
+      .values()
+      .map(|transfer| {
+        transfer
+          .progress
+          .downloaded
+          .saturating_sub(transfer.resumed)
+      })
+      .sum();
+    if received == 0 || elapsed.is_zero() {
+      return None;
+    }
+    Some(elapsed.mul_f64(remaining as f64 / received as f64))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn progress(file: &str, downloaded: u64, total: Option<u64>) -> Progress {
+    Progress {
+      file: PathBuf::from(file),
+      downloaded,
+      total,
+    }
+  }
+
+  #[test]
+  fn transfers() {
+    let mut transfers = Transfers::default();
+    assert_eq!(transfers.fraction(), None);
+
+    transfers.update(progress("lib.so", 0, Some(300)));
+    transfers.update(progress("dictionary.csv", 50, None));
+    assert_eq!(transfers.downloaded(), 50);
+    assert_eq!(transfers.total(), None);
+
+    // dictionary.csv was resumed from 50 bytes, only 150 bytes were received in 15 seconds
+    transfers.update(progress("dictionary.csv", 100, Some(100)));
+    transfers.update(progress("lib.so", 100, Some(300)));
+    assert_eq!(transfers.total(), Some(400));
+    assert_eq!(transfers.fraction(), Some(0.5));
+    assert_eq!(
+      transfers.estimate(Duration::from_secs(15)),
+      Some(Duration::from_secs(20))
+    );
+
+    transfers.clear();
+    assert_eq!(transfers.downloaded(), 0);
+  }
+}
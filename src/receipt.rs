@@ -0,0 +1,141 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{
+  constants::{PATH_RECEIPT, VERSION},
+  utils::sha256_hex,
+};
+
+/// Hook and dictionary jobs finish concurrently, both update the same receipt.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Record of everything the installer wrote into a DF directory, stored in `dfint-data/receipt.json`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Receipt {
+  pub installer_version: String,
+  pub updated: String,
+  pub hook: Option<Record>,
+  pub dictionary: Option<Record>,
+}
+
+/// One installed part: the manifest it came from and the files written.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Record {
+  /// Checksum of the manifest the files were installed from
+  pub checksum: u32,
+  /// DF checksum the hook manifest was picked for
+  pub df: Option<u32>,
+  pub language: Option<String>,
+  pub installed: String,
+  pub files: Vec<FileRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRecord {
+  /// Path relative to the DF directory
+  pub path: PathBuf,
+  pub size: u64,
+  pub sha256: String,
+  pub url: String,
+  /// The local file was kept instead of the downloaded one, `sha256` is of the local file
+  #[serde(default)]
+  pub kept: bool,
+}
+
+impl FileRecord {
+  /// Describe a file already written to `df_dir.join(path)`.
+  pub fn new(df_dir: &Path, path: &Path, url: &str) -> Result<Self> {
+    let data = std::fs::read(df_dir.join(path))?;
+    Ok(Self {
+      path: path.to_path_buf(),
+      size: data.len() as u64,
+      sha256: sha256_hex(&data),
+      url: url.to_string(),
+      kept: false,
+    })
+  }
+}
+
+impl Receipt {
+  fn path(df_dir: &Path) -> PathBuf {
+    df_dir.join(PATH_RECEIPT)
+  }
+
+  pub fn load(df_dir: &Path) -> Option<Self> {
+    let content = std::fs::read_to_string(Self::path(df_dir)).ok()?;
+    serde_json::from_str(&content).ok()
+  }
+
+  fn save(&mut self, df_dir: &Path) -> Result<()> {
+    self.installer_version = VERSION.to_string();
+    self.updated = chrono::Local::now().to_rfc3339();
+    let path = Self::path(df_dir);
+    std::fs::create_dir_all(path.parent().expect("receipt path has a parent"))?;
+    std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+    Ok(())
+  }
+
+  /// Load the receipt of `df_dir`, apply `change` and write it back.
+  pub fn update(df_dir: &Path, change: impl FnOnce(&mut Receipt)) -> Result<()> {
+    let _guard = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let mut receipt = Self::load(df_dir).unwrap_or_default();
+    change(&mut receipt);
+    receipt.save(df_dir)
+  }
+
+  pub fn delete(df_dir: &Path) {
+    let _ = std::fs::remove_file(Self::path(df_dir));
+  }
+
+  pub fn files(&self) -> impl Iterator<Item = &FileRecord> {
+    self
+      .hook
+      .iter()
+      .chain(self.dictionary.iter())
+      .flat_map(|record| record.files.iter())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn update_keeps_other_parts() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(Receipt::load(dir.path()).is_none());
+    std::fs::write(dir.path().join("lib.so"), "hook").unwrap();
+    std::fs::write(dir.path().join("dictionary.csv"), "a,b").unwrap();
+
+    let lib = FileRecord::new(dir.path(), Path::new("lib.so"), "/hook/lib.so").unwrap();
+    assert_eq!(lib.size, 4);
+    Receipt::update(dir.path(), |receipt| {
+      receipt.hook = Some(Record {
+        checksum: 1,
+        df: Some(42),
+        files: vec![lib.clone()],
+        ..Default::default()
+      })
+    })
+    .unwrap();
+    let csv = FileRecord::new(dir.path(), Path::new("dictionary.csv"), "/dict/dictionary.csv").unwrap();
+    Receipt::update(dir.path(), |receipt| {
+      receipt.dictionary = Some(Record {
+        checksum: 2,
+        language: Some("Russian".to_string()),
+        files: vec![csv.clone()],
+        ..Default::default()
+      })
+    })
+    .unwrap();
+
+    let receipt = Receipt::load(dir.path()).unwrap();
+    assert_eq!(receipt.installer_version, VERSION);
+    assert_eq!(receipt.hook.as_ref().unwrap().df, Some(42));
+    assert_eq!(receipt.files().cloned().collect::<Vec<_>>(), vec![lib, csv]);
+
+    Receipt::delete(dir.path());
+    assert!(Receipt::load(dir.path()).is_none());
+  }
+}
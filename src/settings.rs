@@ -0,0 +1,265 @@
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+
+use crate::{
+  agent::{parse_proxy, set_proxy},
+  cache::{DEFAULT_CACHE_LIMIT, set_cache_limit},
+  constants::{BASE_URL, PATH_SETTINGS_FILE},
+  discovery::ScanRoot,
+  installer::set_allow_unhashed,
+  mirrors::set_mirrors,
+};
+
+/// A base URL the data is downloaded from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Source {
+  pub url: String,
+  #[serde(default = "enabled")]
+  pub enabled: bool,
+}
+
+fn enabled() -> bool {
+  true
+}
+
+/// User settings, stored next to the cache file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settings {
+  /// Mirrors in the order they are preferred
+  #[serde(default = "default_sources")]
+  pub sources: Vec<Source>,
+  /// Size limit of the download cache in MB, 0 disables the cache
+  #[serde(default = "default_cache_limit")]
+  pub cache_limit: u64,
+  /// Proxy for all requests, the environment variables are used if not set
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub proxy: Option<String>,
+  /// Executables of the registered DF installations
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub installations: Vec<PathBuf>,
+  /// Folders searched for DF installations besides the common ones
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub scan_roots: Vec<ScanRoot>,
+  /// Install files the manifest has no SHA-256 for, they are only checked by the crc32
+  #[serde(default)]
+  pub allow_unhashed: bool,
+}
+
+fn default_cache_limit() -> u64 {
+  DEFAULT_CACHE_LIMIT
+}
+
+fn default_sources() -> Vec<Source> {
+  BASE_URL
+    .iter()
+    .map(|url| Source {
+      url: url.to_string(),
+      enabled: true,
+    })
+    .collect()
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Self {
+      sources: default_sources(),
+      cache_limit: DEFAULT_CACHE_LIMIT,
+      proxy: None,
+      installations: vec![],
+      scan_roots: vec![],
+      allow_unhashed: false,
+    }
+  }
+}
+
+impl Settings {
+  /// Stored settings, the defaults if there are none or they can't be read.
+  pub fn load() -> Self {
+    Self::load_from(Path::new(PATH_SETTINGS_FILE))
+  }
+
+  fn load_from(path: &Path) -> Self {
+    std::fs::read_to_string(path)
+      .ok()
+      .and_then(|content| serde_json::from_str(&content).ok())
+      .unwrap_or_default()
+  }
+
+  pub fn save(&self) -> Result<()> {
+    self.save_to(Path::new(PATH_SETTINGS_FILE))
+  }
+
+  fn save_to(&self, path: &Path) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+    Ok(())
+  }
+
+  /// Use the enabled sources, the cache limit and the proxy for all requests.
+  pub fn apply(&self) {
+    set_mirrors(self.enabled_urls());
+    set_cache_limit(self.cache_limit);
+    set_allow_unhashed(self.allow_unhashed);
+    // only valid proxies are stored
+    let _ = set_proxy(self.proxy.as_deref());
+  }
+
+  /// Use `proxy` for all requests, the environment variables if it's empty.
+  pub fn set_proxy(&mut self, proxy: &str) -> Result<()> {
+    let proxy = proxy.trim();
+    if proxy.is_empty() {
+      self.proxy = None;
+    } else {
+      parse_proxy(proxy)?;
+      self.proxy = Some(proxy.to_string());
+    }
+    Ok(())
+  }
+
+  pub fn enabled_urls(&self) -> Vec<String> {
+    self
+      .sources
+      .iter()
+      .filter(|source| source.enabled)
+      .map(|source| source.url.clone())
+      .collect()
+  }
+
+  fn position(&self, url: &str) -> Result<usize> {
+    let url = normalize(url);
+    match self.sources.iter().position(|source| source.url == url) {
+      Some(index) => Ok(index),
+      None => bail!("Unknown mirror: {url}"),
+    }
+  }
+
+  /// Add an `http(s)://` or `file://` base URL at `position`, at the end if `None`.
+  pub fn add(&mut self, url: &str, position: Option<usize>) -> Result<()> {
+    let url = normalize(url);
+    if !["http://", "https://", "file://"]
+      .iter()
+      .any(|scheme| url.starts_with(scheme))
+    {
+      bail!("Mirror URL must start with http://, https:// or file://: {url}");
+    }
+    if self.position(&url).is_ok() {
+      bail!("Mirror is already in the list: {url}");
+    }
+    let position = position
+      .unwrap_or(self.sources.len())
+      .min(self.sources.len());
+    self.sources.insert(position, Source { url, enabled: true });
+    Ok(())
+  }
+
+  pub fn remove(&mut self, url: &str) -> Result<()> {
+    let index = self.position(url)?;
+    self.sources.remove(index);
+    Ok(())
+  }
+
+  pub fn set_enabled(&mut self, url: &str, enabled: bool) -> Result<()> {
+    let index = self.position(url)?;
+    self.sources[index].enabled = enabled;
+    Ok(())
+  }
+
+  /// Move a source to `position`, 0 is tried first.
+  pub fn move_to(&mut self, url: &str, position: usize) -> Result<()> {
+    let index = self.position(url)?;
+    let source = self.sources.remove(index);
+    let position = position.min(self.sources.len());
+    self.sources.insert(position, source);
+    Ok(())
+  }
+
+  /// Remember a DF installation, `false` if it's known already.
+  pub fn register(&mut self, path: &Path) -> bool {
+    if self.installations.iter().any(|known| known == path) {
+      return false;
+    }
+    self.installations.push(path.to_path_buf());
+    true
+  }
+
+  pub fn unregister(&mut self, path: &Path) {
+    self.installations.retain(|known| known != path);
+  }
+
+  /// Search `path` for installations, the depth is replaced if it's known already.
+  pub fn add_scan_root(&mut self, path: &Path, depth: usize) {
+    match self.scan_roots.iter_mut().find(|root| root.path == path) {
+      Some(root) => root.depth = depth,
+      None => self.scan_roots.push(ScanRoot::new(path, depth)),
+    }
+  }
+
+  pub fn remove_scan_root(&mut self, path: &Path) {
+    self.scan_roots.retain(|root| root.path != path);
+  }
+}
+
+/// Base URLs are joined with absolute paths, so they have no trailing slash.
+fn normalize(url: &str) -> String {
+  url.trim().trim_end_matches('/').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn edit_sources() {
+    let mut settings = Settings::default();
+    assert_eq!(settings.enabled_urls(), BASE_URL.to_vec());
+
+    settings.add(" http://lan-cache:8080/ ", Some(0)).unwrap();
+    settings.add("file:///srv/dfint", None).unwrap();
+    assert!(settings.add("http://lan-cache:8080", None).is_err());
+    assert!(settings.add("ftp://example.com", None).is_err());
+    assert_eq!(settings.sources[0].url, "http://lan-cache:8080");
+
+    settings.set_enabled(BASE_URL[1], false).unwrap();
+    settings.move_to("file:///srv/dfint/", 1).unwrap();
+    settings.remove(BASE_URL[0]).unwrap();
+    assert!(settings.remove("http://unknown").is_err());
+
+    settings.set_proxy(" socks5://proxy.local:1080 ").unwrap();
+    assert!(settings.set_proxy("ftp://proxy.local").is_err());
+    assert_eq!(settings.proxy.as_deref(), Some("socks5://proxy.local:1080"));
+    settings.set_proxy("").unwrap();
+    assert_eq!(settings.proxy, None);
+
+    assert!(settings.register(Path::new("/games/df/dwarfort")));
+    assert!(!settings.register(Path::new("/games/df/dwarfort")));
+    assert!(settings.register(Path::new("/games/df-test/dwarfort")));
+    settings.unregister(Path::new("/games/df/dwarfort"));
+    assert_eq!(settings.installations, vec![PathBuf::from("/games/df-test/dwarfort")]);
+
+    settings.add_scan_root(Path::new("/games"), 2);
+    settings.add_scan_root(Path::new("/mnt/games"), 3);
+    settings.add_scan_root(Path::new("/games"), 4);
+    settings.remove_scan_root(Path::new("/mnt/games"));
+    assert_eq!(settings.scan_roots, vec![ScanRoot::new("/games", 4)]);
+    assert_eq!(
+      settings.enabled_urls(),
+      vec!["http://lan-cache:8080", "file:///srv/dfint"]
+    );
+  }
+
+  #[test]
+  fn save_and_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings");
+    assert_eq!(Settings::load_from(&path), Settings::default());
+
+    let mut settings = Settings::default();
+    settings.add("file:///srv/dfint", Some(0)).unwrap();
+    settings.save_to(&path).unwrap();
+    assert_eq!(Settings::load_from(&path), settings);
+
+    std::fs::write(&path, r#"{"sources":[{"url":"http://mirror"}]}"#).unwrap();
+    let loaded = Settings::load_from(&path);
+    assert!(loaded.sources[0].enabled);
+    assert_eq!(loaded.cache_limit, DEFAULT_CACHE_LIMIT);
+  }
+}
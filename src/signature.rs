@@ -0,0 +1,80 @@
+use anyhow::{Result, anyhow, bail};
+use ed25519_dalek::{Signature, VerifyingKey};
+
+/// Check a detached ed25519 signature of `data`.
+///
+/// The signature file contains the 64 signature bytes, either raw or hex encoded.
+pub fn verify(data: &[u8], signature: &[u8], public_key: &[u8; 32]) -> Result<()> {
+  let key = VerifyingKey::from_bytes(public_key).map_err(|_| anyhow!("Invalid manifest public key"))?;
+  let signature = Signature::from_bytes(&decode(signature)?);
+  key
+    .verify_strict(data, &signature)
+    .map_err(|_| anyhow!("Manifest signature is invalid"))
+}
+
+fn decode(signature: &[u8]) -> Result<[u8; 64]> {
+  if let Ok(raw) = <[u8; 64]>::try_from(signature) {
+    return Ok(raw);
+  }
+
+  let text = std::str::from_utf8(signature).unwrap_or_default().trim();
+  if text.len() != 128 || !text.is_ascii() {
+    bail!("Malformed manifest signature");
+  }
+  let mut bytes = [0u8; 64];
+  for (index, byte) in bytes.iter_mut().enumerate() {
+    *byte =
+      u8::from_str_radix(&text[index * 2..index * 2 + 2], 16).map_err(|_| anyhow!("Malformed manifest signature"))?;
+  }
+  Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ed25519_dalek::{Signer, SigningKey};
+
+  const MANIFEST: &[u8] = br#"[{"df":1,"checksum":2,"lib":"/a","config":"/b","offsets":"/c","dfhooks":"/d"}]"#;
+
+  fn key_pair(seed: u8) -> (SigningKey, [u8; 32]) {
+    let signing = SigningKey::from_bytes(&[seed; 32]);
+    let public = signing.verifying_key().to_bytes();
+    (signing, public)
+  }
+
+  fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+  }
+
+  #[test]
+  fn valid_signature() {
+    let (signing, public) = key_pair(1);
+    let signature = signing.sign(MANIFEST).to_bytes();
+    assert!(verify(MANIFEST, &signature, &public).is_ok());
+    assert!(verify(MANIFEST, format!("{}\n", hex(&signature)).as_bytes(), &public).is_ok());
+  }
+
+  #[test]
+  fn tampered_manifest() {
+    let (signing, public) = key_pair(1);
+    let signature = signing.sign(MANIFEST).to_bytes();
+    let tampered = String::from_utf8_lossy(MANIFEST).replace("/a", "/x");
+    assert!(verify(tampered.as_bytes(), &signature, &public).is_err());
+  }
+
+  #[test]
+  fn wrong_key() {
+    let (signing, _) = key_pair(1);
+    let (_, other) = key_pair(2);
+    let signature = signing.sign(MANIFEST).to_bytes();
+    assert!(verify(MANIFEST, &signature, &other).is_err());
+  }
+
+  #[test]
+  fn malformed_signature() {
+    let (_, public) = key_pair(1);
+    assert!(verify(MANIFEST, b"", &public).is_err());
+    assert!(verify(MANIFEST, b"not a signature", &public).is_err());
+    assert!(verify(MANIFEST, "zz".repeat(64).as_bytes(), &public).is_err());
+  }
+}
@@ -0,0 +1,152 @@
+//! Local HTTP stand-in for tests of the network code, one request per connection.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct Request {
+  pub method: String,
+  pub path: String,
+  /// Start of a `Range: bytes=<start>-` header
+  pub range: Option<u64>,
+  pub if_none_match: Option<String>,
+}
+
+pub struct Reply {
+  pub status: u16,
+  pub headers: Vec<(String, String)>,
+  pub body: Vec<u8>,
+  /// Drop the connection after sending this many bytes of the body
+  pub cut: Option<usize>,
+  pub delay: Duration,
+}
+
+impl Reply {
+  pub fn status(status: u16) -> Self {
+    Self {
+      status,
+      headers: vec![],
+      body: vec![],
+      cut: None,
+      delay: Duration::ZERO,
+    }
+  }
+
+  pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+    Self {
+      body: body.into(),
+      ..Self::status(200)
+    }
+  }
+
+  /// Serve `body` honouring the range of the request.
+  pub fn file(request: &Request, body: &[u8]) -> Self {
+    match request.range {
+      Some(start) if (start as usize) < body.len() => Self {
+        headers: vec![(
+          "Content-Range".to_string(),
+          format!("bytes {start}-{}/{}", body.len() - 1, body.len()),
+        )],
+        body: body[start as usize..].to_vec(),
+        ..Self::status(206)
+      },
+      Some(_) => Self::status(416),
+      None => Self::ok(body),
+    }
+  }
+}
+
+type Handler = dyn Fn(&Request, usize) -> Reply + Send + Sync;
+
+pub struct TestServer {
+  /// Base URL without a trailing slash
+  pub url: String,
+  requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+  /// Serve requests with `handler`, which also gets the number of requests served before.
+  pub fn start(handler: impl Fn(&Request, usize) -> Reply + Send + Sync + 'static) -> Self {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(vec![]));
+    let handler: Arc<Handler> = Arc::new(handler);
+    let log = requests.clone();
+    std::thread::spawn(move || {
+      for stream in listener.incoming().flatten() {
+        let handler = handler.clone();
+        let log = log.clone();
+        std::thread::spawn(move || serve(stream, handler.as_ref(), &log));
+      }
+    });
+    Self { url, requests }
+  }
+
+  pub fn requests(&self) -> Vec<Request> {
+    self.requests.lock().unwrap().clone()
+  }
+}
+
+/// Address nothing listens on.
+pub fn closed_url() -> String {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  format!("http://{}", listener.local_addr().unwrap())
+}
+
+fn serve(mut stream: TcpStream, handler: &Handler, log: &Mutex<Vec<Request>>) {
+  let mut reader = BufReader::new(stream.try_clone().unwrap());
+  let mut line = String::new();
+  if reader.read_line(&mut line).is_err() {
+    return;
+  }
+  let mut parts = line.split_whitespace();
+  let mut request = Request {
+    method: parts.next().unwrap_or_default().to_string(),
+    path: parts.next().unwrap_or_default().to_string(),
+    range: None,
+    if_none_match: None,
+  };
+  loop {
+    line.clear();
+    if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+      break;
+    }
+    if let Some((name, value)) = line.split_once(':') {
+      if name.eq_ignore_ascii_case("range") {
+        request.range = value
+          .trim()
+          .trim_start_matches("bytes=")
+          .trim_end_matches('-')
+          .parse()
+          .ok();
+      } else if name.eq_ignore_ascii_case("if-none-match") {
+        request.if_none_match = Some(value.trim().to_string());
+      }
+    }
+  }
+
+  let index = {
+    let mut log = log.lock().unwrap();
+    log.push(request.clone());
+    log.len() - 1
+  };
+  let reply = handler(&request, index);
+  std::thread::sleep(reply.delay);
+  let mut head = format!(
+    "HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n",
+    reply.status,
+    reply.body.len()
+  );
+  for (name, value) in &reply.headers {
+    head.push_str(&format!("{name}: {value}\r\n"));
+  }
+  head.push_str("\r\n");
+  let _ = stream.write_all(head.as_bytes());
+  if request.method != "HEAD" {
+    let end = reply.cut.unwrap_or(reply.body.len()).min(reply.body.len());
+    let _ = stream.write_all(&reply.body[..end]);
+  }
+  let _ = stream.flush();
+}
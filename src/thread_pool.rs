@@ -28,6 +28,11 @@ impl<T: Send + 'static> ThreadPool<T> {
     });
   }
 
+  /// Sender for messages produced outside of the executed tasks, e.g. progress reports.
+  pub fn sender(&self) -> Sender<T> {
+    self.tx.clone()
+  }
+
   pub fn poll(&self) -> TryIter<'_, T> {
     self.rx.try_iter()
   }
@@ -0,0 +1,140 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+struct Item {
+  staged: PathBuf,
+  target: PathBuf,
+  previous: Option<PathBuf>,
+}
+
+/// A set of files written to a staging directory first and moved into place together.
+///
+/// If any file can't be placed, the files already placed are removed and the previous
+/// versions are put back. The staging directory is deleted when the transaction is dropped.
+pub struct Transaction {
+  dir: TempDir,
+  items: Vec<Item>,
+}
+
+impl Transaction {
+  /// Staging directory is created inside `root`, so the final moves are renames on the same file system.
+  pub fn new(root: &Path) -> Result<Self> {
+    std::fs::create_dir_all(root)?;
+    let dir = tempfile::Builder::new()
+      .prefix(".staging-")
+      .tempdir_in(root)
+      .with_context(|| format!("Unable to create staging directory in {}", root.display()))?;
+    Ok(Self { dir, items: vec![] })
+  }
+
+  /// Register `target` and return the path in the staging directory the content should be written to.
+  pub fn stage(&mut self, target: &Path) -> PathBuf {
+    let staged = self.dir.path().join(format!("{}.new", self.items.len()));
+    self.items.push(Item {
+      staged: staged.clone(),
+      target: target.to_path_buf(),
+      previous: None,
+    });
+    staged
+  }
+
+  pub fn commit(mut self) -> Result<()> {
+    for index in 0..self.items.len() {
+      if let Err(err) = self.place(index) {
+        self.rollback(index);
+        return Err(err);
+      }
+    }
+    Ok(())
+  }
+
+  fn place(&mut self, index: usize) -> Result<()> {
+    let previous = self.dir.path().join(format!("{index}.old"));
+    let item = &mut self.items[index];
+    if let Some(parent) = item.target.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+    if item.target.exists() {
+      std::fs::rename(&item.target, &previous)
+        .with_context(|| format!("Unable to replace {}", item.target.display()))?;
+      item.previous = Some(previous);
+    }
+    std::fs::rename(&item.staged, &item.target)
+      .with_context(|| format!("Unable to write {}", item.target.display()))?;
+    Ok(())
+  }
+
+  /// Undo the items up to and including `failed`, newest first.
+  fn rollback(&mut self, failed: usize) {
+    for item in self.items[..=failed].iter().rev() {
+      match &item.previous {
+        Some(previous) => {
+          let _ = std::fs::rename(previous, &item.target);
+        }
+        None => {
+          if !item.staged.exists() {
+            let _ = std::fs::remove_file(&item.target);
+          }
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn commit_replaces_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let existing = dir.path().join("a.txt");
+    let created = dir.path().join("sub/b.txt");
+    std::fs::write(&existing, "old").unwrap();
+
+    let mut transaction = Transaction::new(&dir.path().join("data")).unwrap();
+    std::fs::write(transaction.stage(&existing), "new a").unwrap();
+    std::fs::write(transaction.stage(&created), "new b").unwrap();
+    transaction.commit().unwrap();
+
+    assert_eq!(std::fs::read_to_string(&existing).unwrap(), "new a");
+    assert_eq!(std::fs::read_to_string(&created).unwrap(), "new b");
+    assert_eq!(std::fs::read_dir(dir.path().join("data")).unwrap().count(), 0);
+  }
+
+  #[test]
+  fn failure_restores_previous_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let existing = dir.path().join("a.txt");
+    let created = dir.path().join("b.txt");
+    let blocker = dir.path().join("blocker");
+    std::fs::write(&existing, "old").unwrap();
+    std::fs::write(&blocker, "not a directory").unwrap();
+
+    let mut transaction = Transaction::new(dir.path()).unwrap();
+    std::fs::write(transaction.stage(&existing), "new a").unwrap();
+    std::fs::write(transaction.stage(&created), "new b").unwrap();
+    std::fs::write(transaction.stage(&blocker.join("c.txt")), "new c").unwrap();
+    assert!(transaction.commit().is_err());
+
+    assert_eq!(std::fs::read_to_string(&existing).unwrap(), "old");
+    assert!(!created.exists());
+    assert_eq!(std::fs::read_to_string(&blocker).unwrap(), "not a directory");
+  }
+
+  #[test]
+  fn missing_staged_file_aborts() {
+    let dir = tempfile::tempdir().unwrap();
+    let existing = dir.path().join("a.txt");
+    std::fs::write(&existing, "old").unwrap();
+
+    let mut transaction = Transaction::new(dir.path()).unwrap();
+    std::fs::write(transaction.stage(&dir.path().join("b.txt")), "new b").unwrap();
+    transaction.stage(&existing);
+    assert!(transaction.commit().is_err());
+
+    assert_eq!(std::fs::read_to_string(&existing).unwrap(), "old");
+    assert!(!dir.path().join("b.txt").exists());
+  }
+}
@@ -0,0 +1,12 @@
+use eframe::egui::Color32;
+
+pub const GITHUB_ICON: eframe::egui::widgets::ImageSource<'static> =
+  eframe::egui::include_image!("../assets/github.png");
+pub const TRANSIFEX_ICON: eframe::egui::widgets::ImageSource<'static> =
+  eframe::egui::include_image!("../assets/transifex.png");
+pub const APP_ICON: &'static [u8; 1980] = include_bytes!("../assets/df.png");
+
+pub const COLOR_UP_TO_DATE: Color32 = Color32::from_rgb(67, 160, 71);
+pub const COLOR_UPDATE_AVAILABLE: Color32 = Color32::from_rgb(230, 184, 0);
+pub const COLOR_CHOOSE_LANGUAGE: Color32 = Color32::from_rgb(230, 184, 0);
+pub const COLOR_ERROR: Color32 = Color32::from_rgb(211, 47, 47);
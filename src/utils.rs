@@ -1,6 +1,9 @@
 use std::{ffi::OsStr, path::PathBuf};
 
 use anyhow::Result;
+
+use crate::discovery::EXECUTABLES;
+use sha2::{Digest, Sha256};
 use sysinfo::{Process, System};
 
 pub fn checksum_for_files(vec: Vec<PathBuf>) -> Result<u32> {
@@ -14,15 +17,18 @@ pub fn checksum_for_files(vec: Vec<PathBuf>) -> Result<u32> {
   Ok(crc32fast::hash(&data))
 }
 
+pub fn sha256_hex(data: &[u8]) -> String {
+  format!("{:x}", Sha256::digest(data))
+}
+
+/// DF executable in the current directory. Searching the machine is left to `discover`,
+/// which is run on request only.
 pub fn scan_df() -> Option<PathBuf> {
-  let current = std::env::current_dir().unwrap();
-  let pathes = [
-    current.join("Dwarf Fortress.exe"),
-    current.join("dwarfort"),
-    PathBuf::from("C:\\Program Files (x86)\\Steam\\steamapps\\common\\Dwarf Fortress\\Dwarf Fortress.exe"),
-    PathBuf::from("~/.local/share/Steam/steamapps/common/Dwarf Fortress/dwarfort"),
-  ];
-  pathes.iter().find(|path| path.exists()).cloned()
+  let current = std::env::current_dir().ok()?;
+  EXECUTABLES
+    .iter()
+    .map(|name| current.join(name))
+    .find(|path| path.exists())
 }
 
 pub async fn is_df_running() -> bool {
@@ -31,6 +37,8 @@ pub async fn is_df_running() -> bool {
       OsStr::new("Dwarf Fortress.exe"),
       OsStr::new("dwarfort"),
       OsStr::new("Dwarf Fortress."),
+      // executable of the macOS app bundle
+      OsStr::new("Dwarf Fortress"),
     ]
     .contains(&val.name())
   })
@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+  constants::{PATH_DATA, PATH_RECEIPT},
+  receipt::Receipt,
+  utils::sha256_hex,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileState {
+  Unchanged,
+  Missing,
+  Modified,
+  /// Local edit the user chose to keep on the last install
+  Kept,
+  /// Present in the data directory, but not written by the installer
+  Extra,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+  /// Path relative to the DF directory
+  pub path: PathBuf,
+  pub state: FileState,
+}
+
+/// State of the files on disk compared with the install receipt.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Report {
+  pub files: Vec<FileReport>,
+}
+
+impl Report {
+  pub fn new(df_dir: &Path, receipt: Option<&Receipt>) -> Self {
+    let Some(receipt) = receipt else {
+      return Self::default();
+    };
+
+    let mut files: Vec<FileReport> = receipt
+      .files()
+      .map(|record| {
+        let state = match std::fs::read(df_dir.join(&record.path)) {
+          Err(_) => FileState::Missing,
+          Ok(data) if !sha256_hex(&data).eq_ignore_ascii_case(&record.sha256) => FileState::Modified,
+          Ok(_) if record.kept => FileState::Kept,
+          Ok(_) => FileState::Unchanged,
+        };
+        FileReport {
+          path: record.path.clone(),
+          state,
+        }
+      })
+      .collect();
+
+    let mut extra = vec![];
+    collect_files(df_dir, &df_dir.join(PATH_DATA), &mut extra);
+    for path in extra {
+      if path != Path::new(PATH_RECEIPT) && !files.iter().any(|file| file.path == path) {
+        files.push(FileReport {
+          path,
+          state: FileState::Extra,
+        });
+      }
+    }
+
+    Self { files }
+  }
+
+  pub fn state(&self, path: &Path) -> Option<FileState> {
+    self
+      .files
+      .iter()
+      .find(|file| file.path == path)
+      .map(|file| file.state)
+  }
+
+  /// Installed files among `paths` that are missing or were changed after the install.
+  pub fn changed(&self, paths: &[PathBuf]) -> Vec<&FileReport> {
+    self
+      .files
+      .iter()
+      .filter(|file| matches!(file.state, FileState::Missing | FileState::Modified))
+      .filter(|file| paths.contains(&file.path))
+      .collect()
+  }
+}
+
+/// Files below `dir` relative to `root`, skipping the staging and partial download directories.
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return;
+  };
+  for entry in entries.flatten() {
+    let path = entry.path();
+    if path.is_dir() {
+      if !entry.file_name().to_string_lossy().starts_with('.') {
+        collect_files(root, &path, files);
+      }
+    } else if let Ok(relative) = path.strip_prefix(root) {
+      files.push(relative.to_path_buf());
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::receipt::{FileRecord, Record};
+
+  #[test]
+  fn report() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join(PATH_DATA)).unwrap();
+    let mut files = vec![];
+    for path in [
+      "lib.so",
+      "dfint-data/config.toml",
+      "dfint-data/dictionary.csv",
+      "dfint-data/offsets.toml",
+    ] {
+      std::fs::write(dir.path().join(path), "installed").unwrap();
+      files.push(FileRecord::new(dir.path(), Path::new(path), "/url").unwrap());
+    }
+    files[3].kept = true;
+    let receipt = Receipt {
+      hook: Some(Record {
+        files,
+        ..Default::default()
+      }),
+      ..Default::default()
+    };
+
+    std::fs::remove_file(dir.path().join("dfint-data/config.toml")).unwrap();
+    std::fs::write(dir.path().join("dfint-data/dictionary.csv"), "edited").unwrap();
+    std::fs::write(dir.path().join("dfint-data/notes.txt"), "user file").unwrap();
+    std::fs::write(dir.path().join(PATH_RECEIPT), "{}").unwrap();
+
+    let report = Report::new(dir.path(), Some(&receipt));
+    assert_eq!(report.state(Path::new("lib.so")), Some(FileState::Unchanged));
+    assert_eq!(
+      report.state(Path::new("dfint-data/config.toml")),
+      Some(FileState::Missing)
+    );
+    assert_eq!(
+      report.state(Path::new("dfint-data/dictionary.csv")),
+      Some(FileState::Modified)
+    );
+    assert_eq!(
+      report.state(Path::new("dfint-data/offsets.toml")),
+      Some(FileState::Kept)
+    );
+    assert_eq!(report.state(Path::new("dfint-data/notes.txt")), Some(FileState::Extra));
+    assert_eq!(report.state(Path::new(PATH_RECEIPT)), None);
+    assert_eq!(report.files.len(), 5);
+
+    let changed = report.changed(&[PathBuf::from("lib.so"), PathBuf::from("dfint-data/dictionary.csv")]);
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0].path, Path::new("dfint-data/dictionary.csv"));
+  }
+
+  #[test]
+  fn no_receipt() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(Report::new(dir.path(), None).files.is_empty());
+  }
+}